@@ -1,29 +1,49 @@
+mod check;
 mod deserializers;
+mod load;
 
-use cfgen::prelude::*;
 use euclid::Vector2D;
-use formatter::FormatString;
 use hashbrown::HashMap;
-use serde::Deserialize;
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer},
+    Deserialize, Serialize,
+};
+use snafu::{ResultExt, Snafu};
 
 use crate::{
     events::{KeyPress, UserEvent},
     math::Pixels,
+    template::{self, Template},
 };
 
 const DEFAULT: &str = include_str!("../default_config.toml");
 
-#[derive(Deserialize, Debug, Clone, Cfgen)]
+pub use check::check_config;
+pub use load::{watched_paths, LoadError, LoadOptions};
+
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
-#[cfgen(default = "DEFAULT", generate_test = "false")]
 pub struct UserConfig {
     pub status_format: String,
+    pub title_format: String,
     pub show_scrollbars: bool,
     #[serde(with = "deserializers::InterpTypeDef")]
     pub interpolation_algorithm: gdk_pixbuf::InterpType,
 
     pub zoom_step_size: Percent,
 
+    pub error_display_duration: Duration,
+
+    pub keep_failed_images: bool,
+
+    pub slideshow_interval: Duration,
+
+    pub slideshow_loop: bool,
+
+    pub wrap_around: bool,
+
+    pub status_bar: StatusBarEntry,
+
     pub mode: ModeEntry,
 
     // This is read from an user provided config so I'm pretty sure
@@ -32,12 +52,74 @@ pub struct UserConfig {
 }
 
 pub struct Config {
-    pub status_format: FormatString,
+    pub status_format: Template,
+    pub title_format: Template,
+    pub status_bar: StatusBar,
     pub show_scrollbars: bool,
     pub interpolation_algorithm: gdk_pixbuf::InterpType,
     pub zoom_step_size: Percent,
+    pub error_display_duration: Duration,
+    pub keep_failed_images: bool,
+    pub slideshow_interval: Duration,
+    pub slideshow_loop: bool,
+    pub wrap_around: bool,
 
+    pub viewer_mode: ViewerMode,
     pub mode: Mode,
+    /// Initial window size, overrides `mode.geometry`.
+    pub window_size: Option<Vector2D<i32, Pixels>>,
+}
+
+/// Settings given on the command line that take precedence over the config files.
+#[derive(Debug, Default, Clone)]
+pub struct Overrides {
+    pub scaling: Option<ImageScaling>,
+    pub scale: Option<Percent>,
+    pub window_size: Option<Ratio>,
+}
+
+impl Overrides {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(scaling) = self.scaling {
+            config.mode.initial_scaling = scaling;
+        }
+        if let Some(scale) = self.scale {
+            config.mode.geometry.scale = scale;
+        }
+        if let Some(Ratio(size)) = self.window_size {
+            config.window_size = Some(size.cast());
+        }
+    }
+}
+
+/// Parses command line arguments with the same rules as values in the config.
+pub fn parse_arg<T: DeserializeOwned>(arg: &str) -> Result<T, String> {
+    T::deserialize(arg.into_deserializer()).map_err(|e: de::value::Error| e.to_string())
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct StatusBarEntry {
+    pub position: StatusBarPosition,
+    pub left: String,
+    pub center: String,
+    pub css: String,
+}
+
+pub struct StatusBar {
+    pub position: StatusBarPosition,
+    pub left: Template,
+    pub center: Template,
+    pub css: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusBarPosition {
+    Top,
+    Bottom,
+    OverlayTop,
+    OverlayBottom,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -50,17 +132,58 @@ pub struct ModeEntry {
 #[serde(rename_all = "kebab-case")]
 pub struct Mode {
     pub initial_scaling: ImageScaling,
+    pub new_image_view: NewImageView,
+    pub page_layout: PageLayout,
+    /// Scrolling past the bottom shows the next image, past the top the previous one.
+    pub advance_on_scroll_end: bool,
     pub hide_status: bool,
     pub geometry: Geometry,
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+/// What happens to zoom and scroll position when the next image is shown.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "kebab-case")]
+pub enum NewImageView {
+    /// Scale with `initial-scaling` and start at the top.
+    Reset,
+    /// Keep the zoom level and scroll position of the previous image.
+    Keep,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PageLayout {
+    Single,
+    /// Two portrait pages side by side, left to right.
+    Spread,
+    /// Two portrait pages side by side, right to left like manga.
+    SpreadRtl,
+    /// Every page stacked vertically in one continuous strip.
+    Strip,
+}
+
+impl PageLayout {
+    pub fn is_spread(self) -> bool {
+        match self {
+            PageLayout::Spread | PageLayout::SpreadRtl => true,
+            PageLayout::Single | PageLayout::Strip => false,
+        }
+    }
+}
+
+/// Deserialized from its kebab-case name or `zoom: N%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ImageScaling {
     FitToWidth,
     FitToHeight,
     Fit,
     None,
+    /// Like `Fit` but never scales up.
+    ShrinkToFit,
+    /// Covers the whole window, cropping what doesn't fit.
+    Fill,
+    FitToWidthShrinkOnly,
+    Zoom(Percent),
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -70,39 +193,82 @@ pub struct Geometry {
     pub aspect_ratio: Ratio,
 }
 
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
 pub enum ViewerMode {
     Image,
     Archive,
 }
 
+#[derive(Snafu, Debug)]
+pub enum FormatError {
+    #[snafu(display("Bad {} in config: {}", key, source))]
+    BadFormat {
+        key: &'static str,
+        source: template::Error,
+    },
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Ratio(pub Vector2D<f64, Pixels>);
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Percent(pub f64);
 
-const FORMAT_KEYS: &[&str] = &[
-    "width", "height", "filename", "fullpath", "filesize", "index", "nimages",
+#[derive(Clone, Copy, Debug)]
+pub struct Duration(pub std::time::Duration);
+
+pub const FORMAT_KEYS: &[&str] = &[
+    "width", "height", "filename", "fullpath", "filesize", "index", "nimages", "zoom", "rotation",
+    "mtime", "mimetype", "format", "bits", "alpha", "dirname", "archive", "page", "state", "make",
+    "model", "exposure", "iso", "focal", "date", "gps", "lastpage", "slideshow",
 ];
 
 impl UserConfig {
     pub fn split_for_app_use(
         self,
         mode: ViewerMode,
-    ) -> Result<(HashMap<KeyPress, UserEvent>, Config), formatter::Error> {
-        let status_format =
-            FormatString::parse_with_allowed_keys(&self.status_format, FORMAT_KEYS)?;
+    ) -> Result<(HashMap<KeyPress, UserEvent>, Config), FormatError> {
+        let status_format = Template::parse(&self.status_format, FORMAT_KEYS)
+            .context(BadFormat {
+                key: "status-format",
+            })?;
+        let title_format = Template::parse(&self.title_format, FORMAT_KEYS)
+            .context(BadFormat {
+                key: "title-format",
+            })?;
+        let status_bar = StatusBar {
+            position: self.status_bar.position,
+            left: Template::parse(&self.status_bar.left, FORMAT_KEYS)
+                .context(BadFormat {
+                    key: "status-bar.left",
+                })?,
+            center: Template::parse(&self.status_bar.center, FORMAT_KEYS)
+                .context(BadFormat {
+                    key: "status-bar.center",
+                })?,
+            css: self.status_bar.css,
+        };
         Ok((
             self.keymap,
             Config {
                 status_format,
+                title_format,
+                status_bar,
                 show_scrollbars: self.show_scrollbars,
                 zoom_step_size: self.zoom_step_size,
+                error_display_duration: self.error_display_duration,
+                keep_failed_images: self.keep_failed_images,
+                slideshow_interval: self.slideshow_interval,
+                slideshow_loop: self.slideshow_loop,
+                wrap_around: self.wrap_around,
+                viewer_mode: mode,
                 interpolation_algorithm: self.interpolation_algorithm,
                 mode: match mode {
                     ViewerMode::Image => self.mode.image,
                     ViewerMode::Archive => self.mode.archive,
                 },
+                window_size: None,
             },
         ))
     }
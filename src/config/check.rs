@@ -0,0 +1,304 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use toml::Value;
+
+use crate::{
+    config::{
+        load::{self, LoadOptions},
+        Percent, UserConfig, ViewerMode, FORMAT_KEYS,
+    },
+    events::UserEvent,
+    template::Template,
+};
+
+/// Keys holding format strings as (table, key).
+const FORMAT_STRINGS: &[(&str, &str)] = &[
+    ("", "status-format"),
+    ("", "title-format"),
+    ("status-bar", "left"),
+    ("status-bar", "center"),
+];
+
+/// Keys holding percentages, in any table.
+const PERCENT_KEYS: &[&str] = &["zoom-step-size", "scale"];
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub path: Option<PathBuf>,
+    /// 1-based line and column.
+    pub pos: Option<(usize, usize)>,
+    pub msg: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+            if let Some((line, column)) = self.pos {
+                write!(f, "{}:{}:", line, column)?;
+            }
+            f.write_str(" ")?;
+        }
+        f.write_str(&self.msg)
+    }
+}
+
+struct File<'a> {
+    path: &'a Path,
+    content: String,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl<'a> File<'a> {
+    fn report(&mut self, pos: Option<(usize, usize)>, msg: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            path: Some(self.path.to_owned()),
+            pos,
+            msg: msg.into(),
+        });
+    }
+
+    /// 1-based line and column of `key` in `table`, "" being the root table.
+    fn find_key(&self, table: &str, key: &str) -> Option<(usize, usize)> {
+        let mut current = "";
+        for (i, line) in self.content.lines().enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with('[') {
+                current = trimmed
+                    .trim_start_matches('[')
+                    .split(']')
+                    .next()
+                    .unwrap_or("")
+                    .trim();
+                continue;
+            }
+
+            let name = trimmed.split('=').next().unwrap_or("").trim();
+            let name = name.trim_matches(|c: char| c == '"' || c == '\'');
+            if current == table && name == key {
+                return Some((i + 1, line.len() - trimmed.len() + 1));
+            }
+        }
+        None
+    }
+
+    /// Like `find_key` but points to the first character of the string value.
+    fn find_value(&self, table: &str, key: &str) -> Option<(usize, usize)> {
+        let (line, column) = self.find_key(table, key)?;
+        let text = self.content.lines().nth(line - 1)?;
+        let quote = text[column - 1..]
+            .find(|c: char| c == '"' || c == '\'')
+            .map(|quote| column + quote + 1);
+        Some((line, quote.unwrap_or(column)))
+    }
+
+    fn check_keymap(&mut self, keymap: &Value) {
+        let keymap = match keymap.as_table() {
+            Some(keymap) => keymap,
+            None => {
+                let pos = self.find_key("", "keymap");
+                self.report(pos, "keymap must be a table");
+                return;
+            }
+        };
+
+        let mut seen: HashMap<u32, &str> = HashMap::new();
+        for (key, action) in keymap {
+            let pos = self.find_key("keymap", key);
+            let keyval = gdk::keyval_from_name(key);
+            if keyval == 0 {
+                self.report(pos, format!("Can't parse as key: {}", key));
+            } else if let Some(other) = seen.insert(keyval, key) {
+                self.report(pos, format!("`{}` and `{}` are the same key", other, key));
+            }
+
+            if let Err(e) = action.clone().try_into::<UserEvent>() {
+                self.report(pos, format!("Unknown action for `{}`: {}", key, e));
+            }
+        }
+    }
+
+    fn check_format_strings(&mut self, config: &Value) {
+        for &(table, key) in FORMAT_STRINGS {
+            let value = if table.is_empty() {
+                config.get(key)
+            } else {
+                config.get(table).and_then(|table| table.get(key))
+            };
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let pos = self.find_value(table, key);
+            match value.as_str() {
+                Some(format) => {
+                    if let Err(e) = Template::parse(format, FORMAT_KEYS) {
+                        let pos = match (pos, e.column()) {
+                            (Some((line, start)), Some(column)) => Some((line, start + column - 1)),
+                            _ => pos,
+                        };
+                        self.report(pos, format!("Bad {}: {}", key, e));
+                    }
+                }
+                None => self.report(pos, format!("{} must be a string", key)),
+            }
+        }
+    }
+
+    fn check_percentages(&mut self, table_name: &str, table: &Value) {
+        let table = match table.as_table() {
+            Some(table) => table,
+            None => return,
+        };
+
+        for (key, value) in table {
+            if value.is_table() {
+                let nested = if table_name.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", table_name, key)
+                };
+                self.check_percentages(&nested, value);
+            } else if PERCENT_KEYS.contains(&key.as_str()) {
+                if let Err(e) = value.clone().try_into::<Percent>() {
+                    let pos = self.find_value(table_name, key);
+                    self.report(pos, format!("Bad {}: {}", key, e));
+                }
+            }
+        }
+    }
+}
+
+fn check_file(path: &Path, depth: usize, diagnostics: &mut Vec<Diagnostic>) {
+    if depth > load::MAX_INCLUDE_DEPTH {
+        diagnostics.push(Diagnostic {
+            path: Some(path.to_owned()),
+            pos: None,
+            msg: "Includes nested too deeply".to_owned(),
+        });
+        return;
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            diagnostics.push(Diagnostic {
+                path: Some(path.to_owned()),
+                pos: None,
+                msg: format!("Can't read: {}", e),
+            });
+            return;
+        }
+    };
+
+    let mut file = File {
+        path,
+        content,
+        diagnostics,
+    };
+
+    let mut config = match file.content.parse::<Value>() {
+        Ok(config) => config,
+        Err(e) => {
+            // toml counts from 0
+            let pos = e.line_col().map(|(line, column)| (line + 1, column + 1));
+            file.report(pos, e.to_string());
+            return;
+        }
+    };
+
+    let includes = match load::includes(path, &mut config) {
+        Ok(includes) => includes,
+        Err(e) => {
+            let pos = file.find_key("", "include");
+            file.report(pos, e.to_string());
+            Vec::new()
+        }
+    };
+
+    if let Some(keymap) = config.get("keymap") {
+        file.check_keymap(keymap);
+    }
+    file.check_format_strings(&config);
+    file.check_percentages("", &config);
+
+    for include in includes {
+        check_file(&include, depth + 1, file.diagnostics);
+    }
+}
+
+/// Checks every config file that would be loaded with `opts` and then the resulting config.
+pub fn check_config(opts: &LoadOptions) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for path in load::config_paths(opts) {
+        check_file(&path, 0, &mut diagnostics);
+    }
+
+    if diagnostics.is_empty() {
+        let opts = LoadOptions {
+            path: opts.path.clone(),
+            write_default: false,
+        };
+        let result = UserConfig::load(&opts)
+            .map_err(|e| e.to_string())
+            .and_then(|config| {
+                config
+                    .split_for_app_use(ViewerMode::Image)
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(msg) = result {
+            diagnostics.push(Diagnostic {
+                path: None,
+                pos: None,
+                msg,
+            });
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, content: &str) -> Vec<Diagnostic> {
+        let path =
+            std::env::temp_dir().join(format!("iv-check-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let mut diagnostics = Vec::new();
+        check_file(&path, 0, &mut diagnostics);
+        std::fs::remove_file(&path).unwrap();
+        diagnostics
+    }
+
+    #[test]
+    fn accepts_positive_percentages() {
+        let diagnostics = check("positive", "zoom-step-size = \"25%\"\n");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn rejects_percentages_that_are_not_positive() {
+        for value in &["0%", "0.0%", "-5%", "nan%", "inf%"] {
+            let content = format!("zoom-step-size = \"{}\"\n", value);
+            let diagnostics = check("not-positive", &content);
+            assert_eq!(diagnostics.len(), 1, "{}", value);
+            assert_eq!(diagnostics[0].pos, Some((1, 19)));
+            assert!(diagnostics[0].msg.contains("positive number"), "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_zero_scale_in_nested_tables() {
+        let content = "[mode.image.geometry]\nscale = \"0%\"\n";
+        let diagnostics = check("nested", content);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].pos, Some((2, 10)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use euclid::vec2;
 use gdk_pixbuf::InterpType;
@@ -8,7 +8,7 @@ use serde::{
 };
 
 use crate::{
-    config::{Percent, Ratio},
+    config::{parse_arg, Duration, ImageScaling, Percent, Ratio},
     events::KeyPress,
 };
 
@@ -62,9 +62,13 @@ impl<'de> Deserialize<'de> for Percent {
                 if value.ends_with('%') {
                     let to_parse = &value[..value.len() - 1];
                     let ret = to_parse
-                        .parse::<u8>()
+                        .parse::<f64>()
                         .map_err(|e| E::custom(format!("{}", e)))?;
-                    Ok(Percent(ret as f64 / 100.))
+                    if ret.is_finite() && ret > 0. {
+                        Ok(Percent(ret / 100.))
+                    } else {
+                        Err(E::custom("Percent value must be a positive number"))
+                    }
                 } else {
                     Err(E::custom("Percent value must end in `%`"))
                 }
@@ -98,3 +102,103 @@ impl<'de> Deserialize<'de> for Ratio {
         deserializer.deserialize_str(RatioVisitor)
     }
 }
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DurationVisitor;
+        impl<'de> de::Visitor<'de> for DurationVisitor {
+            type Value = Duration;
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a duration like 5s or 500ms")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                let (to_parse, millis_per_unit) = if value.ends_with("ms") {
+                    (&value[..value.len() - 2], 1)
+                } else if value.ends_with('s') {
+                    (&value[..value.len() - 1], 1000)
+                } else {
+                    return Err(E::custom("Duration must end in `s` or `ms`"));
+                };
+                let ret = to_parse
+                    .parse::<u64>()
+                    .map_err(|e| E::custom(format!("{}", e)))?;
+                // glib timeouts take milliseconds as u32
+                match ret.checked_mul(millis_per_unit) {
+                    Some(millis) if millis <= u64::from(u32::max_value()) => {
+                        Ok(Duration(std::time::Duration::from_millis(millis)))
+                    }
+                    _ => Err(E::custom("Duration must be shorter than 49 days")),
+                }
+            }
+        }
+
+        deserializer.deserialize_str(DurationVisitor)
+    }
+}
+
+impl FromStr for ImageScaling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.trim() {
+            "fit-to-width" => ImageScaling::FitToWidth,
+            "fit-to-height" => ImageScaling::FitToHeight,
+            "fit" => ImageScaling::Fit,
+            "none" => ImageScaling::None,
+            "shrink-to-fit" => ImageScaling::ShrinkToFit,
+            "fill" => ImageScaling::Fill,
+            "fit-to-width-shrink-only" => ImageScaling::FitToWidthShrinkOnly,
+            other if other.starts_with("zoom:") => {
+                ImageScaling::Zoom(parse_arg(other["zoom:".len()..].trim())?)
+            }
+            other => return Err(format!("Unknown scaling: {}", other)),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ImageScaling {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ImageScalingVisitor;
+        impl<'de> de::Visitor<'de> for ImageScalingVisitor {
+            type Value = ImageScaling;
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a scaling mode like `fit` or `zoom: 150%`")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(ImageScalingVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_scaling_must_be_positive() {
+        assert_eq!(
+            "zoom: 150%".parse::<ImageScaling>(),
+            Ok(ImageScaling::Zoom(Percent(1.5)))
+        );
+        assert!("zoom: 0%".parse::<ImageScaling>().is_err());
+        assert!("zoom: -10%".parse::<ImageScaling>().is_err());
+    }
+
+    #[test]
+    fn durations_must_fit_a_timeout() {
+        let parse = |s: &str| parse_arg::<Duration>(s).map(|duration| duration.0);
+        assert_eq!(parse("5s"), Ok(std::time::Duration::from_secs(5)));
+        assert_eq!(parse("500ms"), Ok(std::time::Duration::from_millis(500)));
+        assert_eq!(
+            parse("4294967295ms"),
+            Ok(std::time::Duration::from_millis(4_294_967_295))
+        );
+        assert!(parse("4294967296ms").is_err());
+        assert!(parse("18446744073709551s").is_err());
+    }
+}
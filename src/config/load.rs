@@ -0,0 +1,216 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use snafu::{ResultExt, Snafu};
+use toml::Value;
+
+use crate::{
+    config::{UserConfig, DEFAULT},
+    xdg,
+};
+
+const CONFIG_FILE: &str = "iv/config.toml";
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    /// Only read this file instead of the system and user config files.
+    pub path: Option<PathBuf>,
+    /// Write a commented out default config to the user config path if it doesn't exist.
+    pub write_default: bool,
+}
+
+#[derive(Snafu, Debug)]
+pub enum LoadError {
+    #[snafu(display("Can't read {}: {}", path.display(), source))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Can't parse {}: {}", path.display(), source))]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[snafu(display("Invalid config: {}", source))]
+    Invalid { source: toml::de::Error },
+
+    #[snafu(display("Can't write default config to {}: {}", path.display(), source))]
+    WriteDefault {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("`include` in {} must be a list of paths", path.display()))]
+    BadInclude { path: PathBuf },
+
+    #[snafu(display("Includes nested too deeply in {}", path.display()))]
+    IncludeDepth { path: PathBuf },
+}
+
+/// The user config file, e.g. `~/.config/iv/config.toml`.
+pub fn user_config_path() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join(CONFIG_FILE))
+}
+
+/// Every config file that gets layered on top of the defaults, least important first.
+pub fn config_paths(opts: &LoadOptions) -> Vec<PathBuf> {
+    if let Some(path) = &opts.path {
+        return vec![path.clone()];
+    }
+
+    let mut ret: Vec<_> = xdg::config_dirs()
+        .into_iter()
+        .rev()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.exists())
+        .collect();
+    ret.extend(user_config_path().filter(|path| path.exists()));
+    ret
+}
+
+/// Config files and their includes that should trigger a reload when changed,
+/// including the user config if it doesn't exist yet.
+pub fn watched_paths(opts: &LoadOptions) -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    for path in config_paths(opts) {
+        push_with_includes(&path, 0, &mut ret);
+    }
+    if opts.path.is_none() {
+        if let Some(user) = user_config_path() {
+            if !ret.contains(&user) {
+                ret.push(user);
+            }
+        }
+    }
+    ret
+}
+
+/// Adds `path` and every file it includes to `paths`, skipping unreadable includes.
+fn push_with_includes(path: &Path, depth: usize, paths: &mut Vec<PathBuf>) {
+    if depth > MAX_INCLUDE_DEPTH || paths.iter().any(|seen| seen == path) {
+        return;
+    }
+    paths.push(path.to_owned());
+
+    let included = read_toml(path)
+        .and_then(|(_, mut value)| includes(path, &mut value))
+        .unwrap_or_default();
+    for include in included {
+        push_with_includes(&include, depth + 1, paths);
+    }
+}
+
+impl UserConfig {
+    pub fn load(opts: &LoadOptions) -> Result<Self, LoadError> {
+        if opts.path.is_none() && opts.write_default {
+            write_default_if_missing()?;
+        }
+
+        let mut config: Value = DEFAULT.parse().expect("Default config is invalid");
+        for path in config_paths(opts) {
+            let layer = read_with_includes(&path, 0)?;
+            merge(&mut config, layer);
+        }
+
+        config.try_into().context(Invalid)
+    }
+}
+
+fn write_default_if_missing() -> Result<(), LoadError> {
+    if let Some(path) = user_config_path() {
+        if !path.exists() {
+            log::info!("Writing default config to {}", path.display());
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context(WriteDefault { path: &path })?;
+            }
+            fs::write(&path, commented_default()).context(WriteDefault { path: &path })?;
+        }
+    }
+    Ok(())
+}
+
+/// The default config with every setting commented out, so the user config
+/// doesn't override system configs until something gets uncommented.
+fn commented_default() -> String {
+    let mut ret = String::from(
+        "# Uncomment settings to override the system config and the defaults below.\n\n",
+    );
+    for line in DEFAULT.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            ret.push_str(line);
+        } else {
+            ret.push('#');
+            ret.push_str(line);
+        }
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Reads the file at `path` as toml.
+pub fn read_toml(path: &Path) -> Result<(String, Value), LoadError> {
+    let content = fs::read_to_string(path).context(Read { path })?;
+    let value = content.parse().context(Parse { path })?;
+    Ok((content, value))
+}
+
+/// Paths in the `include` list of `value`, relative to the directory of `path`.
+pub fn includes(path: &Path, value: &mut Value) -> Result<Vec<PathBuf>, LoadError> {
+    let include = match value.as_table_mut().and_then(|table| table.remove("include")) {
+        Some(include) => include,
+        None => return Ok(Vec::new()),
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    include
+        .as_array()
+        .and_then(|paths| {
+            paths
+                .iter()
+                .map(|include| include.as_str().map(|include| dir.join(include)))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| LoadError::BadInclude {
+            path: path.to_owned(),
+        })
+}
+
+/// Reads a config file with its includes merged below it.
+fn read_with_includes(path: &Path, depth: usize) -> Result<Value, LoadError> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(LoadError::IncludeDepth {
+            path: path.to_owned(),
+        });
+    }
+
+    let (_, mut value) = read_toml(path)?;
+    let mut ret = Value::Table(Default::default());
+    for include in includes(path, &mut value)? {
+        merge(&mut ret, read_with_includes(&include, depth + 1)?);
+    }
+    merge(&mut ret, value);
+
+    Ok(ret)
+}
+
+/// Merges `layer` into `base`, tables are merged recursively and everything else is replaced.
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Table(base), Value::Table(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
@@ -1,3 +1,12 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
 use euclid::vec2;
 use futures::{future, prelude::*};
 use gdk_pixbuf::Pixbuf;
@@ -5,22 +14,113 @@ use gio::prelude::*;
 use slotmap::DefaultKey;
 use snafu::{ResultExt, Snafu};
 
-use crate::events::Event;
+use crate::{
+    events::Event,
+    metadata,
+    session::{self, Session},
+};
+
+/// Metadata requests older than the newest few are dropped when paging quickly.
+const MAX_PENDING_METADATA: usize = 2;
+/// How long the view has to stay the same before the last session is saved.
+const AUTOSAVE_DEBOUNCE: Duration = Duration::from_millis(500);
 
 pub struct AppCtx {
     g_ctx: glib::MainContext,
     event_tx: glib::Sender<Event>,
+    metadata_tx: mpsc::Sender<(DefaultKey, String)>,
+    autosave_tx: Option<mpsc::Sender<Session>>,
+    autosave_thread: Option<thread::JoinHandle<()>>,
+    autosave_timer: Rc<RefCell<Option<glib::SourceId>>>,
 }
 
 impl AppCtx {
     pub fn new(event_tx: glib::Sender<Event>) -> Self {
+        let (metadata_tx, metadata_rx) = mpsc::channel();
+        let tx = event_tx.clone();
+        thread::spawn(move || read_metadata(metadata_rx, tx));
+
+        let (autosave_tx, autosave_rx) = mpsc::channel();
+        let autosave_thread = thread::spawn(move || write_sessions(autosave_rx));
+
         Self {
             g_ctx: glib::MainContext::default(),
             event_tx,
+            metadata_tx,
+            autosave_tx: Some(autosave_tx),
+            autosave_thread: Some(autosave_thread),
+            autosave_timer: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+impl AppCtx {
+    /// Saves `session` as the last session without blocking the UI.
+    pub fn autosave(&self, session: Session) {
+        if let Some(tx) = &self.autosave_tx {
+            let _ = tx.send(session);
+        }
+    }
+
+    /// Sends `Event::Autosave` once this wasn't called for a while.
+    pub fn schedule_autosave(&self) {
+        self.cancel_autosave_timer();
+        let tx = self.event_tx.clone();
+        let autosave_timer = self.autosave_timer.clone();
+        let timer = glib::timeout_add_local(AUTOSAVE_DEBOUNCE.as_millis() as u32, move || {
+            autosave_timer.borrow_mut().take();
+            let _ = tx.send(Event::Autosave);
+            glib::Continue(false)
+        });
+        *self.autosave_timer.borrow_mut() = Some(timer);
+    }
+
+    fn cancel_autosave_timer(&self) {
+        if let Some(timer) = self.autosave_timer.borrow_mut().take() {
+            glib::source_remove(timer);
+        }
+    }
+
+    /// Waits until every session passed to `autosave` is written, later ones are dropped.
+    pub fn finish_autosave(&mut self) {
+        self.cancel_autosave_timer();
+        self.autosave_tx = None;
+        if let Some(thread) = self.autosave_thread.take() {
+            let _ = thread.join();
         }
     }
 }
 
+impl AppCtx {
+    /// Sends `Event::ConfigChanged` whenever one of `paths` changes.
+    /// Watching stops when the returned monitors are dropped.
+    pub fn watch_files(&self, paths: &[PathBuf]) -> Vec<gio::FileMonitor> {
+        paths
+            .iter()
+            .filter_map(|path| {
+                let monitor = gio::File::new_for_path(path)
+                    .monitor_file(gio::FileMonitorFlags::NONE, None::<&gio::Cancellable>);
+                match monitor {
+                    Ok(monitor) => Some(monitor),
+                    Err(e) => {
+                        log::warn!("Can't watch {}: {}", path.display(), e);
+                        None
+                    }
+                }
+            })
+            .inspect(|monitor| {
+                let tx = self.event_tx.clone();
+                // creating a file is followed by a hint as well so only the hint counts
+                monitor.connect_changed(move |_, _, _, event| {
+                    if event == gio::FileMonitorEvent::ChangesDoneHint {
+                        let _ = tx.send(Event::ConfigChanged);
+                    }
+                });
+            })
+            .collect()
+    }
+}
+
 async fn load_image(stream: gio::FileInputStream) -> Result<Pixbuf, glib::Error> {
     Pixbuf::new_from_stream_async_future(&stream).await
 }
@@ -28,8 +128,10 @@ async fn load_image(stream: gio::FileInputStream) -> Result<Pixbuf, glib::Error>
 impl AppCtx {
     pub fn load_image(&self, id: DefaultKey, path: String) -> future::AbortHandle {
         let g_path = gio::File::new_for_path(&path);
+        let exif_path = path.clone();
 
         let tx = self.event_tx.clone();
+        let metadata_tx = self.metadata_tx.clone();
 
         let fut = async move {
             let open = async move { g_path.read_async_future(glib::PRIORITY_LOW).await };
@@ -40,16 +142,31 @@ impl AppCtx {
                 (Ok(fh), Ok(Some(info))) => {
                     // FIXME: just hoping this works is bad
                     let file_meta = fh
-                        .query_info_async_future("standard::size", glib::PRIORITY_LOW)
+                        .query_info_async_future(
+                            "standard::size,time::modified",
+                            glib::PRIORITY_LOW,
+                        )
                         .await
                         .unwrap();
+                    let (format, width, height) = info;
                     let _ = tx.send(Event::ImageMeta {
                         id,
                         meta: crate::ImageMeta {
-                            dimensions: vec2(info.1, info.2),
+                            dimensions: vec2(width, height),
                             filesize: file_meta.get_size(),
+                            mtime: file_meta.get_attribute_uint64("time::modified"),
+                            mime_type: format
+                                .get_mime_types()
+                                .first()
+                                .map(|mime| mime.to_string())
+                                .unwrap_or_default(),
+                            format_name: format
+                                .get_name()
+                                .map(|name| name.to_string())
+                                .unwrap_or_default(),
                         },
                     });
+                    let _ = metadata_tx.send((id, exif_path));
 
                     match load_image(fh).await.context(FromGlib) {
                         Ok(img) => Event::ImageLoaded { img, id },
@@ -71,6 +188,34 @@ impl AppCtx {
     }
 }
 
+/// Reads metadata on its own thread because the exif crate only does blocking io.
+fn read_metadata(requests: mpsc::Receiver<(DefaultKey, String)>, tx: glib::Sender<Event>) {
+    while let Ok(first) = requests.recv() {
+        let pending: Vec<_> = std::iter::once(first).chain(requests.try_iter()).collect();
+        let skip = pending.len().saturating_sub(MAX_PENDING_METADATA);
+        for (id, path) in pending.into_iter().skip(skip) {
+            match metadata::read(Path::new(&path)) {
+                Ok(meta) => {
+                    let _ = tx.send(Event::Metadata { id, meta });
+                }
+                Err(e) => {
+                    log::debug!("No metadata for {}: {}", path, e);
+                }
+            }
+        }
+    }
+}
+
+fn write_sessions(sessions: mpsc::Receiver<Session>) {
+    while let Ok(first) = sessions.recv() {
+        // only the newest one is worth writing
+        let session = sessions.try_iter().last().unwrap_or(first);
+        if let Err(e) = session::save(session::LAST, &session) {
+            log::error!("Can't save session: {}", e);
+        }
+    }
+}
+
 #[derive(Snafu, Debug)]
 pub enum LoadError {
     #[snafu(display("Error from glib: {}", source))]
@@ -1,9 +1,15 @@
+use std::cell::Cell;
+
+use hashbrown::HashMap;
 use linked_slotlist::DefaultKey;
-use serde::Deserialize;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    forward_to_deserialize_any, Deserialize,
+};
 
 use crate::context::LoadError;
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "kebab-case")]
 pub enum UserEvent {
     Quit,
@@ -29,11 +35,157 @@ pub enum UserEvent {
     RotateCounterClockwise,
     RotateUpsideDown,
     ToggleFullscreen,
+    RetryLoad,
+    ToggleMetadata,
+    ReloadConfig,
+    ToggleHelp,
+    ToggleKeepView,
+    SaveSession,
+    ToggleSlideshow,
+    Goto,
+    PickFile,
+}
+
+impl UserEvent {
+    pub const ALL: &'static [UserEvent] = &[
+        UserEvent::Quit,
+        UserEvent::Next,
+        UserEvent::Previous,
+        UserEvent::JumpToStart,
+        UserEvent::JumpToEnd,
+        UserEvent::Goto,
+        UserEvent::PickFile,
+        UserEvent::ToggleSlideshow,
+        UserEvent::RetryLoad,
+        UserEvent::ScaleToFitCurrent,
+        UserEvent::OriginalSize,
+        UserEvent::ToggleKeepView,
+        UserEvent::ResizeToFitImage,
+        UserEvent::ZoomIn,
+        UserEvent::ZoomOut,
+        UserEvent::ScrollDown,
+        UserEvent::ScrollUp,
+        UserEvent::ScrollLeft,
+        UserEvent::ScrollRight,
+        UserEvent::ScrollVStart,
+        UserEvent::ScrollVEnd,
+        UserEvent::ScrollHStart,
+        UserEvent::ScrollHEnd,
+        UserEvent::RotateClockwise,
+        UserEvent::RotateCounterClockwise,
+        UserEvent::RotateUpsideDown,
+        UserEvent::ToggleFullscreen,
+        UserEvent::ToggleStatus,
+        UserEvent::ToggleMetadata,
+        UserEvent::ToggleHelp,
+        UserEvent::ReloadConfig,
+        UserEvent::SaveSession,
+    ];
+
+    /// Name as used in the keymap.
+    pub fn name(self) -> &'static str {
+        variant_names()[self as usize]
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            UserEvent::Quit => "Quit iv",
+            UserEvent::Next => "Show the next image",
+            UserEvent::Previous => "Show the previous image",
+            UserEvent::ScaleToFitCurrent => "Scale the image to fit the window",
+            UserEvent::OriginalSize => "Show the image at its original size",
+            UserEvent::ResizeToFitImage => "Resize the window to the size of the image",
+            UserEvent::ZoomOut => "Zoom out by zoom-step-size",
+            UserEvent::ZoomIn => "Zoom in by zoom-step-size",
+            UserEvent::ScrollDown => "Scroll down",
+            UserEvent::ScrollUp => "Scroll up",
+            UserEvent::ScrollLeft => "Scroll left",
+            UserEvent::ScrollRight => "Scroll right",
+            UserEvent::ScrollVStart => "Scroll to the top",
+            UserEvent::ScrollVEnd => "Scroll to the bottom",
+            UserEvent::ScrollHStart => "Scroll to the left edge",
+            UserEvent::ScrollHEnd => "Scroll to the right edge",
+            UserEvent::ToggleStatus => "Show or hide the status bar",
+            UserEvent::JumpToStart => "Show the first image",
+            UserEvent::JumpToEnd => "Show the last image",
+            UserEvent::RotateClockwise => "Rotate by 90 degrees clockwise",
+            UserEvent::RotateCounterClockwise => "Rotate by 90 degrees counter clockwise",
+            UserEvent::RotateUpsideDown => "Rotate by 180 degrees",
+            UserEvent::ToggleFullscreen => "Toggle fullscreen",
+            UserEvent::RetryLoad => "Try loading a failed image again",
+            UserEvent::ToggleMetadata => "Show or hide the metadata panel",
+            UserEvent::ReloadConfig => "Reload the config files",
+            UserEvent::ToggleHelp => "Show or hide this help",
+            UserEvent::ToggleKeepView => "Keep zoom and scroll position for the next images",
+            UserEvent::SaveSession => "Save the image list and view under a name",
+            UserEvent::ToggleSlideshow => "Start or stop the slideshow",
+            UserEvent::Goto => "Jump to an index, a relative offset or a file name",
+            UserEvent::PickFile => "Choose an image by fuzzy searching its file name",
+        }
+    }
+}
+
+/// Every action with the names of the keys bound to it.
+pub fn bindings(keymap: &HashMap<KeyPress, UserEvent>) -> Vec<(UserEvent, Vec<String>)> {
+    UserEvent::ALL
+        .iter()
+        .map(|&action| {
+            let mut keys: Vec<_> = keymap
+                .iter()
+                .filter(|(_, bound)| **bound == action)
+                .map(|(key, _)| key.name())
+                .collect();
+            keys.sort();
+            (action, keys)
+        })
+        .collect()
+}
+
+/// Names of the `UserEvent` variants in declaration order, taken from the
+/// derived `Deserialize` so they can't drift apart from the keymap.
+fn variant_names() -> &'static [&'static str] {
+    struct Variants<'a>(&'a Cell<&'static [&'static str]>);
+
+    impl<'de, 'a> Deserializer<'de> for Variants<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("only enums are supported"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            self.0.set(variants);
+            Err(de::Error::custom("only collecting variant names"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    let names = Cell::new(&[][..]);
+    let _ = UserEvent::deserialize(Variants(&names));
+    names.get()
 }
 
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 pub struct KeyPress(pub u32);
 
+impl KeyPress {
+    pub fn name(self) -> String {
+        gdk::keyval_name(self.0)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("{:#x}", self.0))
+    }
+}
+
 pub enum Event {
     User(UserEvent),
     ImageLoaded {
@@ -44,10 +196,53 @@ pub enum Event {
         meta: crate::ImageMeta,
         id: DefaultKey,
     },
+    Metadata {
+        meta: crate::metadata::Metadata,
+        id: DefaultKey,
+    },
     LoadFailed {
         id: DefaultKey,
         err: LoadError,
     },
     Quit,
-    WindowFullScreenToggle,
+    /// The image area changed size and settled.
+    ImageAreaResized,
+    /// The image area was scrolled vertically.
+    StripScrolled,
+    /// A tick of the slideshow timer passed.
+    SlideshowTick,
+    /// Text entered in the goto prompt.
+    Goto(String),
+    /// Name entered in the session name prompt.
+    SaveSession(String),
+    /// Index of the image chosen in the file picker.
+    Picked(usize),
+    /// The view stopped changing for a while.
+    Autosave,
+    ConfigChanged,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_arg;
+
+    #[test]
+    fn all_lists_every_action_once() {
+        assert_eq!(UserEvent::ALL.len(), variant_names().len());
+        for (i, action) in UserEvent::ALL.iter().enumerate() {
+            assert!(
+                !UserEvent::ALL[..i].contains(action),
+                "{:?} is listed twice",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for &action in UserEvent::ALL {
+            assert_eq!(parse_arg::<UserEvent>(action.name()), Ok(action));
+        }
+    }
 }
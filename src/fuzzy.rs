@@ -0,0 +1,104 @@
+//! Fuzzy matching of file names for the file picker.
+
+const MATCH: i64 = 1;
+const CONSECUTIVE_BONUS: i64 = 4;
+const WORD_START_BONUS: i64 = 3;
+const MAX_GAP_PENALTY: i64 = 3;
+
+fn lower(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_word_start(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => " _-.".contains(prev) || (prev.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+/// Matches the chars of `pattern` in order anywhere in `candidate`, ignoring case.
+///
+/// Returns a score, higher being better, and the char indices of the matched chars.
+pub fn score(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let mut wanted = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(lower)
+        .peekable();
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut prev = None;
+
+    for (i, c) in candidate.chars().enumerate() {
+        let next = match wanted.peek() {
+            Some(&next) => next,
+            None => break,
+        };
+
+        if lower(c) == next {
+            wanted.next();
+            score += MATCH;
+            match positions.last() {
+                Some(&last) if last + 1 == i => score += CONSECUTIVE_BONUS,
+                Some(&last) => score -= i64::min((i - last - 1) as i64, MAX_GAP_PENALTY),
+                None => {}
+            }
+            if is_word_start(prev, c) {
+                score += WORD_START_BONUS;
+            }
+            positions.push(i);
+        }
+        prev = Some(c);
+    }
+
+    if wanted.peek().is_some() {
+        None
+    } else {
+        Some((score, positions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(pattern: &str, candidate: &str) -> i64 {
+        score(pattern, candidate).unwrap().0
+    }
+
+    fn positions(pattern: &str, candidate: &str) -> Option<Vec<usize>> {
+        score(pattern, candidate).map(|(_, positions)| positions)
+    }
+
+    #[test]
+    fn matches_subsequences_ignoring_case() {
+        assert_eq!(positions("abc", "aXbXc"), Some(vec![0, 2, 4]));
+        assert_eq!(positions("ABC", "abc"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("a c", "abc"), Some(vec![0, 2]));
+        assert_eq!(score("", "abc"), Some((0, vec![])));
+    }
+
+    #[test]
+    fn rejects_missing_or_reordered_chars() {
+        assert_eq!(score("abd", "abc"), None);
+        assert_eq!(score("ba", "ab"), None);
+        assert_eq!(score("abc", ""), None);
+    }
+
+    #[test]
+    fn prefers_prefixes() {
+        assert!(points("pic", "picture.png") > points("pic", "epic.png"));
+    }
+
+    #[test]
+    fn prefers_word_starts() {
+        assert!(points("fb", "foo_bar.png") > points("fb", "fooxbar.png"));
+        assert!(points("fb", "fooBar.png") > points("fb", "foobar.png"));
+    }
+
+    #[test]
+    fn prefers_contiguous_matches() {
+        assert!(points("cat", "cat.png") > points("cat", "c_a_t.png"));
+        assert!(points("cat", "c_a_t.png") > points("cat", "cxxxxaxxxxt.png"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
+use euclid::vec2;
+use gdk_pixbuf::Pixbuf;
+use snafu::{ResultExt, Snafu};
+
+use crate::{
+    metadata,
+    template::{self, Template, Values},
+    ImageMeta,
+};
+
+pub enum Output {
+    Format(Template),
+    Json,
+}
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("Can't read {}: {}", path, source))]
+    Stat {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("{}: Image format not supported or not an image", path))]
+    Unsupported { path: String },
+
+    #[snafu(display("Can't format info for {}: {}", path, source))]
+    Render {
+        path: String,
+        source: template::Error,
+    },
+
+    #[snafu(display("Can't serialize info for {}: {}", path, source))]
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("{} of {} files couldn't be read", failed, total))]
+    Failed { failed: usize, total: usize },
+}
+
+fn read_info(path: &str) -> Result<Values, Error> {
+    let (format, width, height) = Pixbuf::get_file_info(path).ok_or_else(|| Error::Unsupported {
+        path: path.to_owned(),
+    })?;
+    let file_meta = fs::metadata(path).context(Stat { path })?;
+    let mtime = file_meta
+        .modified()
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|mtime| mtime.as_secs())
+        .unwrap_or(0);
+
+    let meta = ImageMeta {
+        dimensions: vec2(width, height),
+        filesize: file_meta.len() as i64,
+        mtime,
+        mime_type: format
+            .get_mime_types()
+            .first()
+            .map(|mime| mime.to_string())
+            .unwrap_or_default(),
+        format_name: format
+            .get_name()
+            .map(|name| name.to_string())
+            .unwrap_or_default(),
+    };
+
+    let mut values = Values::new();
+    crate::insert_meta(&mut values, &meta);
+    crate::insert_path(&mut values, Path::new(path));
+    match metadata::read(Path::new(path)) {
+        Ok(exif) => crate::insert_exif(&mut values, &exif),
+        Err(e) => log::debug!("No metadata for {}: {}", path, e),
+    }
+
+    Ok(values)
+}
+
+/// Prints the info for every image in `images` to stdout, one line each.
+pub fn run(images: &[String], output: &Output) -> Result<(), Error> {
+    let mut failed = 0;
+    for (i, path) in images.iter().enumerate() {
+        let line = read_info(path).and_then(|mut values| {
+            values.insert("index", (i + 1) as f64);
+            values.insert("nimages", images.len() as f64);
+            match output {
+                Output::Format(format) => format.render(&values).context(Render { path }),
+                Output::Json => serde_json::to_string(&values).context(Json { path }),
+            }
+        });
+
+        match line {
+            Ok(line) => println!("{}", line),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        Err(Error::Failed {
+            failed,
+            total: images.len(),
+        })
+    } else {
+        Ok(())
+    }
+}
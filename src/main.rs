@@ -2,19 +2,33 @@
 mod config;
 mod context;
 mod events;
+mod fuzzy;
+mod info;
 mod math;
+mod metadata;
+mod positions;
+mod session;
+mod state;
+mod template;
 mod widgets;
+mod xdg;
 
-use std::{convert::TryFrom, path::Path};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use cascade::cascade;
-use cfgen::prelude::CfgenDefault;
 use euclid::{vec2, Vector2D};
-use formatter::FormatMap;
 use futures::future;
-use gdk_pixbuf::Pixbuf;
+use gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
 use glib::prelude::*;
 use gtk::prelude::*;
+use hashbrown::HashMap;
 use linked_slotlist::{DefaultKey, LinkedSlotlist};
 use slotmap::SecondaryMap;
 use snafu::{ResultExt, Snafu};
@@ -23,50 +37,95 @@ use structopt::StructOpt;
 use crate::{
     context::AppCtx,
     events::{Event, KeyPress},
+    template::{Template, Values},
 };
 use math::Pixels;
-use widgets::Scroll;
-
-fn gtk_run() -> Result<(), Error> {
-    let (_, config) = config::UserConfig::load_or_write_default().context(ReadConfig)?;
-    let opt = Opt::from_args();
-    let mode = {
-        let probably_wants_to_read_archive = opt.images.iter().take(4).all(|file| {
-            // clashes with something in gio so scoped import here
-            use std::os::unix::prelude::*;
-
-            let ext = Path::new(file).extension();
-            ext.map(|ext| ext.as_bytes())
-                .map(|ext| ext == b"cbz" || ext == b"zip")
-                .unwrap_or(false)
-        });
+use widgets::{PromptKind, Scroll, ScrollV, Segment};
+
+/// How long the image area has to keep its size before the image gets rescaled.
+const RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+/// Resolution of the slideshow timer, fine enough for intervals below a second.
+const SLIDESHOW_TICK: Duration = Duration::from_millis(100);
+/// Pages kept loaded on each side of the visible ones in strip layout.
+const STRIP_KEEP: usize = 2;
+
+fn gtk_run(mut opt: Opt) -> Result<(), Error> {
+    let load_options = opt.load_options();
+    let config = config::UserConfig::load(&load_options).context(ReadConfig)?;
+    let mut session = match &opt.restore {
+        Some(name) => {
+            let name = name.as_ref().map_or(session::LAST, String::as_str);
+            Some(session::load(name).context(RestoreSession)?)
+        }
+        None => None,
+    };
+    if let Some(session) = &mut session {
+        if !opt.images.is_empty() {
+            log::warn!("Ignoring images given together with --restore");
+        }
+        opt.images = std::mem::replace(&mut session.images, Vec::new());
+    }
+
+    let mode = opt.mode.or_else(|| session.as_ref().map(|session| session.mode));
+    let mode = mode.unwrap_or_else(|| {
+        let probably_wants_to_read_archive = opt
+            .images
+            .iter()
+            .take(4)
+            .all(|file| is_archive(Path::new(file)));
 
         if probably_wants_to_read_archive {
             config::ViewerMode::Archive
         } else {
             config::ViewerMode::Image
         }
-    };
-    let (keymap, config) = config.split_for_app_use(mode).context(Format)?;
+    });
+    let overrides = opt.overrides();
+    let (keymap, mut config) = config.split_for_app_use(mode).context(Format)?;
+    overrides.apply(&mut config);
 
-    let main = widgets::Main::new();
+    if !config.status_bar.css.is_empty() {
+        let provider = gtk::CssProvider::new();
+        provider
+            .load_from_data(config.status_bar.css.as_bytes())
+            .context(Css)?;
+        if let Some(screen) = gdk::Screen::get_default() {
+            gtk::StyleContext::add_provider_for_screen(
+                &screen,
+                &provider,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+            );
+        }
+    }
+
+    let main = widgets::Main::new(config.status_bar.position);
     let (main_tx, main_rx) = glib::MainContext::channel(glib::source::PRIORITY_DEFAULT);
     let tx = main_tx.clone();
     let window = cascade! {
         gtk::Window::new(gtk::WindowType::Toplevel);
         ..add(main.as_ref());
-        ..set_title("iv - ");
+        ..set_title("iv");
         ..connect_delete_event(move |_, _| {
             let _ = tx.send(Event::Quit);
             Inhibit(false)
         });
     };
 
+    let keymap = Rc::new(RefCell::new(keymap));
     let tx = main_tx.clone();
-    window.connect_key_press_event(move |_, key_evt| {
+    let window_keymap = keymap.clone();
+    window.connect_key_press_event(move |window, key_evt| {
+        // text entries like the goto prompt handle their own keys
+        if window
+            .get_focus()
+            .map_or(false, |widget| widget.is::<gtk::Entry>())
+        {
+            return Inhibit(false);
+        }
+
         let keypress = KeyPress(key_evt.get_keyval());
         log::debug!("{:?}", &keypress);
-        if let Some(user_event) = keymap.get(&keypress) {
+        if let Some(user_event) = window_keymap.borrow().get(&keypress) {
             let _ = tx.send(Event::User(*user_event));
             Inhibit(true)
         } else {
@@ -75,27 +134,71 @@ fn gtk_run() -> Result<(), Error> {
     });
 
     let tx = main_tx.clone();
-    window.connect_window_state_event(move |_, evt| {
-        if evt
-            .get_changed_mask()
-            .contains(gdk::WindowState::FULLSCREEN)
-        {
-            let _ = tx.send(Event::WindowFullScreenToggle);
-        }
+    main.connect_prompt(move |kind, input| {
+        let _ = tx.send(match kind {
+            PromptKind::Goto => Event::Goto(input),
+            PromptKind::SessionName => Event::SaveSession(input),
+        });
+    });
+
+    let tx = main_tx.clone();
+    main.connect_picker(move |index| {
+        let _ = tx.send(Event::Picked(index));
+    });
 
-        Inhibit(false)
+    let tx = main_tx.clone();
+    main.connect_image_resized(RESIZE_DEBOUNCE, move || {
+        let _ = tx.send(Event::ImageAreaResized);
     });
 
     let tx = main_tx.clone();
-    let ctx = AppCtx::new(tx);
+    main.connect_scrolled(move || {
+        let _ = tx.send(Event::StripScrolled);
+    });
+
+    let tx = main_tx.clone();
+    let mut ctx = AppCtx::new(tx);
+    let config_monitors = ctx.watch_files(&config::watched_paths(&load_options));
 
     let images: LinkedSlotlist<_> = opt.images.into_iter().collect();
-    let cursor = images.head();
+    let start_at = opt
+        .start_at
+        .or_else(|| session.as_ref().map(|session| session.index + 1))
+        .unwrap_or(1)
+        .max(1)
+        - 1;
+    let (cursor, index) = match iter_keys(&images).nth(start_at) {
+        Some(cursor) => (Some(cursor), Some(start_at)),
+        None => (images.tail(), images.tail().map(|_| images.len() - 1)),
+    };
+    let saved_view = session
+        .as_ref()
+        .map(|session| (session.zoom, session.scroll));
+    let sticky_scaling = match saved_view {
+        Some((Some(_), _)) => None,
+        _ => Some(config.mode.initial_scaling),
+    };
+    let restore_view = saved_view.map(|(zoom, scroll)| View {
+        scale: zoom.unwrap_or(1.),
+        scroll,
+    });
+    let keep_view = config.mode.new_image_view == config::NewImageView::Keep;
+    let strip = match config.mode.page_layout {
+        config::PageLayout::Strip => Some(Strip {
+            pages: iter_keys(&images).collect(),
+            originals: SecondaryMap::new(),
+            loading: SecondaryMap::new(),
+            window: 0..0,
+            positioned: false,
+        }),
+        _ => None,
+    };
     let mut app = App {
         cursor,
-        index: cursor.map(|_| 0),
-        format_map: default_format_map(),
+        index,
+        format_map: Values::new(),
         state: match cursor {
+            Some(_) if strip.is_some() => State::Strip,
             Some(cursor) => State::LoadingImage {
                 abort_handle: ctx.load_image(cursor, images.get(cursor).unwrap().to_owned()),
                 last_transition: ImageTransition::Next,
@@ -103,25 +206,66 @@ fn gtk_run() -> Result<(), Error> {
             None => State::NoImages,
         },
         images_meta: SecondaryMap::with_capacity(images.len()),
+        images_exif: SecondaryMap::new(),
         filenames: SecondaryMap::with_capacity(images.len()),
+        failed: SecondaryMap::new(),
+        skipped: Vec::new(),
         images,
         config,
+        keymap,
+        load_options,
+        config_monitors,
+        overrides,
         is_fullscreen: false,
+        rotation: session.map_or(0, |session| session.rotation),
+        sticky_scaling,
+        keep_view,
+        last_view: None,
+        restore_view,
+        pending_scroll: None,
+        spread: None,
+        strip,
+        remember_positions: match mode {
+            config::ViewerMode::Archive => true,
+            config::ViewerMode::Image => false,
+        },
+        restore_positions: !opt.from_start,
+        slideshow: None,
     };
+    if app.restore_view.is_none() {
+        app.restore_position();
+    }
+    app.load_partner(&ctx);
 
     window.show_all();
-    let ratio = gtk_win_scale(
-        &window.get_window().unwrap(),
-        app.config.mode.geometry.aspect_ratio.0,
-        app.config.mode.geometry.scale.0,
-    )
-    .unwrap();
-    window.resize(ratio.x, ratio.y);
+    if let State::LoadingImage { .. } = app.state {
+        main.set_loading(true);
+    }
+    main.set_help(&events::bindings(&app.keymap.borrow()));
+    let size = app.config.window_size.unwrap_or_else(|| {
+        gtk_win_scale(
+            &window.get_window().unwrap(),
+            app.config.mode.geometry.aspect_ratio.0,
+            app.config.mode.geometry.scale.0,
+        )
+        .unwrap()
+    });
+    window.resize(size.x, size.y);
+    if app.strip.is_some() {
+        main.show_strip(app.images.len(), size.y);
+    }
+    if opt.fullscreen {
+        app.toggle_fullscreen(&window);
+    }
 
     let tx = main_tx.clone();
     main_rx.attach(None, move |event| {
         match event {
             Event::Quit => {
+                app.save_position(&main);
+                app.autosave_session(&ctx, &main);
+                ctx.finish_autosave();
+                app.print_failed_summary();
                 gtk::main_quit();
             }
             Event::User(action) => {
@@ -146,24 +290,70 @@ fn gtk_run() -> Result<(), Error> {
                     }
                     UserEvent::ZoomIn => {
                         app.zoom_in(&main);
+                        app.update_info(&main);
                     }
                     UserEvent::ZoomOut => {
                         app.zoom_out(&main);
+                        app.update_info(&main);
+                    }
+                    UserEvent::RotateClockwise => {
+                        app.rotate(&main, 90);
+                    }
+                    UserEvent::RotateCounterClockwise => {
+                        app.rotate(&main, 270);
+                    }
+                    UserEvent::RotateUpsideDown => {
+                        app.rotate(&main, 180);
                     }
                     UserEvent::ScaleToFitCurrent => {
                         app.scale_to_fit(&main);
+                        app.update_info(&main);
                     }
                     UserEvent::ToggleFullscreen => {
                         app.toggle_fullscreen(&window);
                     }
+                    UserEvent::RetryLoad => {
+                        app.retry_load(&ctx, &main);
+                    }
+                    UserEvent::ToggleMetadata => {
+                        main.toggle_metadata();
+                    }
+                    UserEvent::ReloadConfig => {
+                        app.reload_config(&ctx, &main);
+                    }
+                    UserEvent::ToggleHelp => {
+                        main.toggle_help();
+                    }
+                    UserEvent::Goto => {
+                        main.show_prompt(PromptKind::Goto);
+                    }
+                    UserEvent::PickFile => {
+                        main.show_picker(app.file_names());
+                    }
+                    UserEvent::ToggleSlideshow => {
+                        app.toggle_slideshow(&main, &tx);
+                    }
+                    UserEvent::SaveSession => {
+                        main.show_prompt(PromptKind::SessionName);
+                    }
+                    UserEvent::ToggleKeepView => {
+                        app.keep_view = !app.keep_view;
+                    }
                     other => {
                         if let Ok(scroll) = Scroll::try_from(other) {
+                            if let Scroll::V(direction) = scroll {
+                                if app.scroll_past_end(&ctx, &main, direction) {
+                                    return Continue(true);
+                                }
+                            }
                             main.scroll(scroll);
                         } else {
                             log::debug!("Unhandled user input: {:?}", other);
                         }
                     }
                 }
+                // zoom, rotation and scrolling end up in the session too
+                ctx.schedule_autosave();
             }
 
             Event::ImageMeta { meta, id } => {
@@ -172,22 +362,68 @@ fn gtk_run() -> Result<(), Error> {
                 app.update_info(&main);
             }
 
+            Event::Metadata { meta, id } if app.wants_metadata(id) => {
+                app.images_exif.insert(id, meta);
+                if app.cursor == Some(id) {
+                    app.update_metadata_panel(&main);
+                    app.update_info(&main);
+                }
+            }
+            // read for an image that was paged past in the meantime
+            Event::Metadata { .. } => {}
+
+            Event::LoadFailed { id, err } if app.strip.is_some() => {
+                app.strip_failed(&main, id, err.to_string());
+            }
+
             Event::LoadFailed { id, err } => {
+                let was_partner = app.is_partner(id);
+                if was_partner {
+                    app.partner_failed(&main);
+                } else if app.is_currently_loading_image(id) {
+                    app.cancel_spread();
+                }
+
+                if app.config.keep_failed_images {
+                    let err = err.to_string();
+                    if let Some(path) = app.images.get(id) {
+                        log::error!("Failed loading image {}: {}", path, err);
+                    }
+                    if app.is_currently_loading_image(id) {
+                        main.set_loading(false);
+                        main.show_broken(&err);
+                        app.state = State::Broken;
+                    }
+                    app.failed.insert(id, err);
+                    app.update_info(&main);
+                    return Continue(true);
+                }
+
                 if app.is_currently_loading_image(id) {
                     match app.state {
-                        State::DisplayImage { .. } | State::NoImages => {
-                            panic!("how did you even get here?")
-                        }
+                        State::DisplayImage { .. }
+                        | State::NoImages
+                        | State::Broken
+                        | State::Strip => panic!("how did you even get here?"),
                         State::LoadingImage {
                             last_transition, ..
-                        } => match last_transition {
-                            ImageTransition::Next | ImageTransition::Start => {
-                                app.try_load(&ctx, &main, ImageTransition::Next);
+                        } => {
+                            let (ahead, back) = match last_transition {
+                                ImageTransition::Next
+                                | ImageTransition::Start
+                                | ImageTransition::Current
+                                | ImageTransition::Jump(..) => {
+                                    (ImageTransition::Next, ImageTransition::Prev)
+                                }
+                                ImageTransition::Prev | ImageTransition::End => {
+                                    (ImageTransition::Prev, ImageTransition::Next)
+                                }
+                            };
+                            // no wrapping around or end of list message for a failure
+                            if !app.load_transition(&ctx, &main, ahead) {
+                                app.load_transition(&ctx, &main, back);
                             }
-                            ImageTransition::Prev | ImageTransition::End => {
-                                app.try_load(&ctx, &main, ImageTransition::Prev);
-                            }
-                        },
+                        }
                     };
                 }
                 if let (Some(path), _, _) = (
@@ -195,25 +431,87 @@ fn gtk_run() -> Result<(), Error> {
                     app.images_meta.remove(id),
                     app.filenames.remove(id),
                 ) {
-                    // FIXME: when rapidly going through images this seems to break
-                    app.index = app.index.map(|index| index - 1);
+                    app.images_exif.remove(id);
+                    // the failed image can be before or after the cursor
+                    app.index = app
+                        .cursor
+                        .and_then(|cur| iter_keys(&app.images).position(|key| key == cur));
                     log::error!("Failed loading image {}: {}", path, err);
-                    // removed the last image
-                    if app.images.head().is_none() {
-                        app.state = State::NoImages;
+                    main.show_error(
+                        &format!("Failed loading {}: {}", path, err),
+                        app.config.error_display_duration.0,
+                    );
+                    app.skipped.push((path, err.to_string()));
+                    if app.cursor == Some(id) {
+                        // there was nothing to move on to
+                        app.cursor = None;
+                        if !app.load_transition(&ctx, &main, ImageTransition::Start) {
+                            app.state = State::NoImages;
+                            main.set_loading(false);
+                            app.update_info(&main);
+                        }
                     }
                 }
             }
 
+            Event::ImageLoaded { id, img } if app.strip.is_some() => {
+                app.strip_loaded(&main, id, img);
+            }
+
             Event::ImageLoaded { id, img } => {
-                if app.is_currently_loading_image(id) {
-                    app.state = State::DisplayImage { img, scale: 100. };
-                    app.scale_initial(&main);
+                if app.is_partner(id) {
+                    if let Some(spread) = &mut app.spread {
+                        spread.second = Page::Loaded(img);
+                    }
+                    app.show_spread_if_ready(&main);
+                } else if app.is_currently_loading_image(id) {
+                    match &mut app.spread {
+                        Some(spread) => {
+                            spread.first = Some(img);
+                            app.show_spread_if_ready(&main);
+                        }
+                        None => {
+                            main.set_loading(false);
+                            app.show_loaded(&main, img);
+                            app.update_info(&main);
+                            ctx.schedule_autosave();
+                        }
+                    }
+                }
+            }
+            Event::Goto(input) => {
+                app.goto(&ctx, &main, &input);
+            }
+            Event::SaveSession(name) => {
+                app.save_named_session(&main, name.trim());
+            }
+            Event::Picked(index) => {
+                if let Some(key) = iter_keys(&app.images).nth(index) {
+                    app.try_load(&ctx, &main, ImageTransition::Jump(key, index));
                 }
             }
-            // FIXME: this doesn't work for some reason
-            Event::WindowFullScreenToggle => {
-                app.scale_initial(&main);
+            Event::SlideshowTick => {
+                app.slideshow_tick(&ctx, &main);
+            }
+            Event::StripScrolled => {
+                app.strip_scrolled(&ctx, &main);
+                ctx.schedule_autosave();
+            }
+            Event::Autosave => {
+                app.autosave_session(&ctx, &main);
+            }
+            Event::ConfigChanged => {
+                app.reload_config(&ctx, &main);
+            }
+            Event::ImageAreaResized if app.strip.is_some() => {
+                app.strip_rescale(&main);
+                app.strip_scrolled(&ctx, &main);
+            }
+            Event::ImageAreaResized => {
+                if app.sticky_scaling.is_some() {
+                    app.rescale(&main);
+                    app.update_info(&main);
+                }
             }
         }
         Continue(true)
@@ -239,19 +537,187 @@ pub fn gtk_win_scale(
 struct App {
     cursor: Option<DefaultKey>,
     is_fullscreen: bool,
+    /// Clockwise rotation in degrees applied to every displayed image.
+    rotation: u32,
     index: Option<usize>,
     images: LinkedSlotlist<String>,
     images_meta: SecondaryMap<DefaultKey, ImageMeta>,
+    images_exif: SecondaryMap<DefaultKey, metadata::Metadata>,
     filenames: SecondaryMap<DefaultKey, String>,
+    /// Images that couldn't be loaded but are kept in the list, with their error message.
+    failed: SecondaryMap<DefaultKey, String>,
+    /// Paths and errors of images that were removed from the list after failing to load.
+    skipped: Vec<(String, String)>,
     state: State,
     config: config::Config,
-    format_map: FormatMap,
+    /// Shared with the key press handler so it can be swapped on config reload.
+    keymap: Rc<RefCell<HashMap<KeyPress, events::UserEvent>>>,
+    load_options: config::LoadOptions,
+    /// Watches the config files and their includes, dropping them stops watching.
+    config_monitors: Vec<gio::FileMonitor>,
+    overrides: config::Overrides,
+    /// Scaling reapplied when the image area changes size, `None` after zooming manually.
+    sticky_scaling: Option<config::ImageScaling>,
+    /// Show new images like the previous one instead of with the initial scaling.
+    keep_view: bool,
+    /// Zoom and scroll position of the last displayed image.
+    last_view: Option<View>,
+    /// Zoom and scroll position for the loading image, from a session or a remembered archive.
+    restore_view: Option<View>,
+    /// Where to scroll once the image that's loading is shown.
+    pending_scroll: Option<ScrollV>,
+    /// Second page shown next to the cursor, only with a spread page layout.
+    spread: Option<Spread>,
+    /// All pages stacked vertically, only with the strip page layout.
+    strip: Option<Strip>,
+    /// Remember zoom and scroll of each archive when leaving it, only in archive mode.
+    remember_positions: bool,
+    /// Show archives where they were left off, off with `--from-start`.
+    restore_positions: bool,
+    slideshow: Option<Slideshow>,
+    format_map: Values,
+}
+
+struct Slideshow {
+    timer: glib::SourceId,
+    /// Time left until the next image, doesn't run down while loading.
+    remaining: Duration,
+}
+
+struct Strip {
+    /// Every image in list order.
+    pages: Vec<DefaultKey>,
+    /// Unscaled loaded pages in `window` so they can be rescaled when the window changes size.
+    originals: SecondaryMap<DefaultKey, Pixbuf>,
+    loading: SecondaryMap<DefaultKey, future::AbortHandle>,
+    /// Positions of the pages kept loaded around the viewport.
+    window: Range<usize>,
+    /// Whether the strip was scrolled to the start page yet.
+    positioned: bool,
+}
+
+struct Spread {
+    partner: DefaultKey,
+    /// The page at the cursor once it's loaded.
+    first: Option<Pixbuf>,
+    second: Page,
+}
+
+enum Page {
+    Loading(future::AbortHandle),
+    Loaded(Pixbuf),
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct View {
+    scale: f64,
+    scroll: (f64, f64),
 }
 
 #[derive(Debug)]
 pub struct ImageMeta {
     dimensions: Vector2D<i32, Pixels>,
     filesize: i64,
+    /// Modification time in seconds since the epoch.
+    mtime: u64,
+    mime_type: String,
+    format_name: String,
+}
+
+fn insert_meta(format_map: &mut Values, meta: &ImageMeta) {
+    format_map.insert("width", meta.dimensions.x as f64);
+    format_map.insert("height", meta.dimensions.y as f64);
+    format_map.insert("filesize", meta.filesize as f64);
+    let mtime = glib::DateTime::new_from_unix_local(meta.mtime as i64)
+        .format("%Y-%m-%d %H:%M")
+        .map(|mtime| mtime.to_string())
+        .unwrap_or_default();
+    format_map.insert("mtime", mtime);
+    format_map.insert("mimetype", meta.mime_type.clone());
+    format_map.insert("format", meta.format_name.clone());
+}
+
+fn clear_meta(format_map: &mut Values) {
+    for &key in &["width", "height", "filesize", "mtime", "mimetype", "format"] {
+        format_map.remove(key);
+    }
+}
+
+/// Keys of `images` from head to tail.
+fn iter_keys<T>(images: &LinkedSlotlist<T>) -> impl Iterator<Item = DefaultKey> + '_ {
+    std::iter::successors(images.head(), move |&cur| images.next(cur))
+}
+
+fn is_archive(path: &Path) -> bool {
+    // clashes with something in gio so scoped import here
+    use std::os::unix::prelude::*;
+
+    let ext = path.extension();
+    ext.map(|ext| ext.as_bytes())
+        .map(|ext| ext == b"cbz" || ext == b"zip")
+        .unwrap_or(false)
+}
+
+fn dirname(path: &Path) -> String {
+    path.parent()
+        .and_then(|parent| parent.file_name())
+        .map(|dirname| dirname.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn insert_path(format_map: &mut Values, path: &Path) {
+    let filename = path
+        .file_name()
+        .map(|filename| filename.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    format_map.insert("filename", filename);
+    format_map.insert("dirname", dirname(path));
+    format_map.insert("fullpath", path.to_string_lossy().into_owned());
+}
+
+fn insert_exif(format_map: &mut Values, exif: &metadata::Metadata) {
+    let fields = [
+        ("make", &exif.make),
+        ("model", &exif.model),
+        ("exposure", &exif.exposure),
+        ("iso", &exif.iso),
+        ("focal", &exif.focal_length),
+        ("date", &exif.date),
+        ("gps", &exif.gps),
+    ];
+    for &(key, value) in &fields {
+        if let Some(value) = value {
+            format_map.insert(key, value.clone());
+        }
+    }
+}
+
+fn clear_exif(format_map: &mut Values) {
+    for &key in &["make", "model", "exposure", "iso", "focal", "date", "gps"] {
+        format_map.remove(key);
+    }
+}
+
+fn render(format: &Template, format_map: &Values) -> Option<String> {
+    match format.render(format_map) {
+        Ok(text) => Some(text),
+        Err(e) => {
+            log::error!("Can't format: {}", e);
+            None
+        }
+    }
+}
+
+fn rotate(img: &Pixbuf, degrees: u32) -> Option<Pixbuf> {
+    use gdk_pixbuf::PixbufRotation;
+    let rotation = match degrees % 360 {
+        90 => PixbufRotation::Clockwise,
+        180 => PixbufRotation::Upsidedown,
+        270 => PixbufRotation::Counterclockwise,
+        _ => return None,
+    };
+    img.rotate_simple(rotation)
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -260,29 +726,97 @@ enum ImageTransition {
     Prev,
     Start,
     End,
+    Current,
+    /// To an image and its index.
+    Jump(DefaultKey, usize),
 }
 
 impl App {
-    fn try_transition(&self, transition: ImageTransition) -> Option<DefaultKey> {
+    /// Number of pages shown at the cursor, two for a spread.
+    fn pages_shown(&self) -> usize {
+        match &self.spread {
+            Some(Spread {
+                second: Page::Failed,
+                ..
+            })
+            | None => 1,
+            Some(_) => 2,
+        }
+    }
+
+    /// Whether `id` can be one of two pages in a spread, pages that weren't loaded
+    /// yet are assumed to be portrait.
+    fn is_pairable(&self, id: DefaultKey) -> bool {
+        let is_portrait = self
+            .images_meta
+            .get(id)
+            .map_or(true, |meta| meta.dimensions.x <= meta.dimensions.y);
+        is_portrait && !self.failed.contains_key(id)
+    }
+
+    /// Target of `transition` and how many images it moves.
+    fn try_transition(&self, transition: ImageTransition) -> Option<(DefaultKey, usize)> {
         match (transition, self.cursor) {
-            (ImageTransition::Prev, Some(cur)) => self.images.prev(cur),
-            (ImageTransition::Next, Some(cur)) => self.images.next(cur),
-            (ImageTransition::Start, _) => self.images.head(),
-            (ImageTransition::End, _) => self.images.tail(),
+            (ImageTransition::Prev, Some(cur)) => {
+                let steps = if self.config.mode.page_layout.is_spread() {
+                    let before =
+                        std::iter::successors(self.images.prev(cur), |&key| self.images.prev(key));
+                    spread_steps_back(before.map(|key| self.is_pairable(key)))
+                } else {
+                    1
+                };
+                // stop at the head if there's only one page left
+                std::iter::successors(Some(cur), |&key| self.images.prev(key))
+                    .take(steps + 1)
+                    .enumerate()
+                    .last()
+                    .filter(|&(n, _)| n > 0)
+                    .map(|(n, key)| (key, n))
+            }
+            (ImageTransition::Next, Some(cur)) => {
+                let steps = self.pages_shown();
+                std::iter::successors(Some(cur), |&key| self.images.next(key))
+                    .nth(steps)
+                    .map(|key| (key, steps))
+            }
+            (ImageTransition::Start, _) => self.images.head().map(|key| (key, 0)),
+            (ImageTransition::End, _) => self.images.tail().map(|key| (key, 0)),
+            (ImageTransition::Current, cur) => cur.map(|key| (key, 0)),
+            (ImageTransition::Jump(key, _), _) => Some((key, 0)),
             _ => None,
         }
     }
 
+    /// Turns `Next` on the last and `Prev` on the first image into `Start` and `End`
+    /// if wrap-around is on.
+    fn wrap(&self, transition: ImageTransition) -> ImageTransition {
+        let wrapped = match transition {
+            ImageTransition::Next => ImageTransition::Start,
+            ImageTransition::Prev => ImageTransition::End,
+            other => return other,
+        };
+        let target = self.try_transition(wrapped).map(|(key, _)| key);
+        if self.config.wrap_around
+            && self.try_transition(transition).is_none()
+            && target != self.cursor
+        {
+            wrapped
+        } else {
+            transition
+        }
+    }
+
     fn change_index(&mut self, transition: ImageTransition) -> Option<DefaultKey> {
         let ret = self.try_transition(transition);
         self.index = match (transition, ret) {
-            (ImageTransition::Prev, Some(_)) => self.index.map(|idx| idx - 1),
-            (ImageTransition::Next, Some(_)) => self.index.map(|idx| idx + 1),
+            (ImageTransition::Prev, Some((_, n))) => self.index.map(|idx| idx - n),
+            (ImageTransition::Next, Some((_, n))) => self.index.map(|idx| idx + n),
             (ImageTransition::Start, Some(_)) => Some(0),
             (ImageTransition::End, Some(_)) => Some(self.images.len() - 1),
+            (ImageTransition::Jump(_, index), Some(_)) => Some(index),
             _ => self.index,
         };
-        ret
+        ret.map(|(key, _)| key)
     }
 
     fn update_info(&mut self, main: &widgets::Main) {
@@ -291,30 +825,89 @@ impl App {
         }
 
         self.format_map.insert("nimages", self.images.len() as f64);
+        self.format_map.insert("rotation", self.rotation as f64);
+
+        let state = match self.state {
+            State::LoadingImage { .. } => "loading",
+            State::Broken => "failed",
+            State::NoImages | State::DisplayImage { .. } | State::Strip => "",
+        };
+        self.format_map.insert("state", state.to_string());
+
+        match &self.slideshow {
+            Some(slideshow) => {
+                let remaining = slideshow.remaining.as_secs_f64().ceil();
+                self.format_map.insert("slideshow", remaining);
+            }
+            None => self.format_map.remove("slideshow"),
+        }
+
+        match &self.state {
+            State::DisplayImage { img, scale } => {
+                self.format_map.insert("zoom", scale * 100.);
+                self.format_map
+                    .insert("bits", img.get_bits_per_sample() as f64);
+                let alpha = if img.get_has_alpha() { "yes" } else { "no" };
+                self.format_map.insert("alpha", alpha.to_string());
+            }
+            _ => {
+                self.format_map.remove("zoom");
+                self.format_map.remove("bits");
+                self.format_map.remove("alpha");
+            }
+        }
+
         if let Some(cur) = self.cursor {
-            if let Some(meta) = self.images_meta.get(cur) {
-                self.format_map.insert("width", meta.dimensions.x as f64);
-                self.format_map.insert("height", meta.dimensions.x as f64);
-                self.format_map.insert("filesize", meta.filesize as f64);
+            match self.images_meta.get(cur) {
+                Some(meta) => insert_meta(&mut self.format_map, meta),
+                None => clear_meta(&mut self.format_map),
             }
 
-            if let Some(filename) = self.filenames.get(cur) {
-                self.format_map.insert("filename", filename.clone());
+            clear_exif(&mut self.format_map);
+            if let Some(exif) = self.images_exif.get(cur) {
+                insert_exif(&mut self.format_map, exif);
             }
 
             if let Some(path) = self.images.get(cur) {
-                self.format_map.insert("fullpath", path.to_owned());
+                let path = Path::new(path);
+                if !self.filenames.contains_key(cur) {
+                    if let Some(filename) = path.file_name() {
+                        self.filenames
+                            .insert(cur, filename.to_string_lossy().into_owned());
+                    }
+                }
+
+                insert_path(&mut self.format_map, path);
+                if let config::ViewerMode::Archive = self.config.viewer_mode {
+                    let archive = path
+                        .file_name()
+                        .filter(|_| is_archive(path))
+                        .map(|archive| archive.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    self.format_map.insert("archive", archive);
+                    if let Some(idx) = self.index {
+                        self.format_map.insert("page", (idx + 1) as f64);
+                        self.format_map
+                            .insert("lastpage", (idx + self.pages_shown()) as f64);
+                    }
+                }
             }
         }
 
-        match self.config.status_format.fmt(&self.format_map) {
-            Ok(fmt) => {
-                main.set_info(&format!("{}", fmt));
-            }
-            Err(e) => {
-                log::error!("Can't format: {}", e);
+        let segments = [
+            (Segment::Left, &self.config.status_bar.left),
+            (Segment::Center, &self.config.status_bar.center),
+            (Segment::Right, &self.config.status_format),
+        ];
+        for (segment, format) in &segments {
+            if let Some(text) = render(format, &self.format_map) {
+                main.set_status(*segment, &text);
             }
         }
+
+        if let Some(title) = render(&self.config.title_format, &self.format_map) {
+            main.set_title(&title);
+        }
     }
 
     fn is_currently_loading_image(&self, id: DefaultKey) -> bool {
@@ -327,66 +920,473 @@ impl App {
         main: &widgets::Main,
         transition: ImageTransition,
     ) {
-        if let Some(cur) = self.change_index(transition) {
-            let path = self.images.get(cur).unwrap().to_owned();
-            if let None = self.filenames.get(cur) {
-                let filename = Path::new(&path).file_name().unwrap().to_str().unwrap();
-                self.filenames.insert(cur, filename.to_owned());
+        let transition = self.wrap(transition);
+        if !self.load_transition(ctx, main, transition) {
+            let end = match transition {
+                ImageTransition::Next => "last",
+                ImageTransition::Prev => "first",
+                _ => return,
+            };
+            main.show_message(
+                &format!("Reached the {} image", end),
+                self.config.error_display_duration.0,
+            );
+        }
+    }
+
+    /// Moves to and loads the image `transition` leads to without wrapping around,
+    /// returns whether there was one.
+    fn load_transition(
+        &mut self,
+        ctx: &context::AppCtx,
+        main: &widgets::Main,
+        transition: ImageTransition,
+    ) -> bool {
+        self.pending_scroll = None;
+        let cur = match self.change_index(transition) {
+            Some(cur) => cur,
+            None => return false,
+        };
+        self.restart_slideshow_interval();
+        if self.strip.is_some() {
+            self.cursor = Some(cur);
+            if let Some(index) = self.index {
+                main.scroll_to_strip_page(index);
             }
+            self.update_info(main);
+            return true;
+        }
+
+        let path = self.images.get(cur).unwrap().to_owned();
+        self.cancel_spread();
+        self.save_position(main);
+        match &self.state {
+            State::LoadingImage { abort_handle, .. } => abort_handle.abort(),
+            State::DisplayImage { scale, .. } => {
+                self.last_view = Some(View {
+                    scale: *scale,
+                    scroll: main.scroll_position(),
+                });
+            }
+            State::NoImages | State::Broken | State::Strip => {}
+        }
+        self.cursor = Some(cur);
+        self.restore_view = None;
+        self.restore_position();
+        ctx.schedule_autosave();
+        self.update_metadata_panel(main);
+
+        if let Some(err) = self.failed.get(cur) {
+            self.state = State::Broken;
+            main.show_broken(err);
+            main.set_loading(false);
             self.update_info(&main);
-            self.state = match &self.state {
-                State::NoImages => State::NoImages,
-                State::LoadingImage { abort_handle, .. } => {
-                    abort_handle.abort();
-                    State::LoadingImage {
-                        abort_handle: ctx.load_image(cur, path),
-                        last_transition: transition,
-                    }
-                }
-                State::DisplayImage { .. } => State::LoadingImage {
+            return true;
+        }
+
+        self.state = match &self.state {
+            State::NoImages => State::NoImages,
+            State::Strip => unreachable!("strip layout doesn't load single images"),
+            State::LoadingImage { .. } | State::DisplayImage { .. } | State::Broken => {
+                State::LoadingImage {
                     abort_handle: ctx.load_image(cur, path),
                     last_transition: transition,
-                },
-            };
-            self.cursor = Some(cur);
-            main.set_image(None);
+                }
+            }
+        };
+        self.load_partner(ctx);
+        main.set_image(None);
+        main.set_loading(true);
+        self.update_info(&main);
+        true
+    }
+
+    /// Moves to the image at a 1-based index, a relative offset like `+10` or `-5`
+    /// or the next one with `input` in its file name.
+    fn goto(&mut self, ctx: &context::AppCtx, main: &widgets::Main, input: &str) {
+        let input = input.trim();
+        if input.is_empty() {
+            return;
+        }
+
+        match self.goto_target(input) {
+            Some((key, index)) => self.try_load(ctx, main, ImageTransition::Jump(key, index)),
+            None => main.show_error(
+                &format!("No image matching `{}`", input),
+                self.config.error_display_duration.0,
+            ),
         }
     }
 
-    fn zoom_in(&mut self, main: &widgets::Main) {
-        if let State::DisplayImage { img, scale } = &self.state {
-            self.state = {
-                let next = math::step_next(*scale, self.config.zoom_step_size.0);
-                let img_px: euclid::Vector2D<_, Pixels> = vec2(img.get_width(), img.get_height());
-                let scaled = (img_px.to_f64() * next).cast();
-                let resized = img
-                    .scale_simple(scaled.x, scaled.y, self.config.interpolation_algorithm)
-                    .unwrap();
-                main.set_image(Some(&resized));
-                State::DisplayImage {
-                    img: img.clone(),
-                    scale: next,
+    fn goto_target(&self, input: &str) -> Option<(DefaultKey, usize)> {
+        let len = self.images.len();
+        if len == 0 {
+            return None;
+        }
+
+        let offset = match input.chars().next() {
+            Some('+') | Some('-') => input.parse::<isize>().ok(),
+            _ => None,
+        };
+        let index = match (offset, input.parse::<usize>()) {
+            (Some(offset), _) => {
+                let index = self.index.unwrap_or(0) as isize + offset;
+                Some(index.max(0).min(len as isize - 1) as usize)
+            }
+            (None, Ok(index)) => Some(index.max(1).min(len) - 1),
+            (None, Err(_)) => None,
+        };
+        if let Some(index) = index {
+            return iter_keys(&self.images).nth(index).map(|key| (key, index));
+        }
+
+        // search file names starting after the current image
+        let needle = input.to_lowercase();
+        let keys: Vec<_> = iter_keys(&self.images).collect();
+        let start = self.index.map_or(0, |index| index + 1);
+        (0..len).map(|i| (start + i) % len).find_map(|index| {
+            let key = keys[index];
+            let name = Path::new(self.images.get(key)?).file_name()?;
+            if name.to_string_lossy().to_lowercase().contains(&needle) {
+                Some((key, index))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// File names of all images in order, for the file picker.
+    fn file_names(&self) -> Vec<String> {
+        iter_keys(&self.images)
+            .filter_map(|key| self.images.get(key))
+            .map(|path| {
+                Path::new(path)
+                    .file_name()
+                    .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned())
+            })
+            .collect()
+    }
+
+    /// Whether `id` is still shown or loading so its metadata is worth keeping.
+    fn wants_metadata(&self, id: DefaultKey) -> bool {
+        let in_strip = self.strip.as_ref().map_or(false, |strip| {
+            strip.originals.contains_key(id) || strip.loading.contains_key(id)
+        });
+        self.cursor == Some(id) || in_strip
+    }
+
+    fn is_partner(&self, id: DefaultKey) -> bool {
+        self.spread.as_ref().map(|spread| spread.partner) == Some(id)
+    }
+
+    /// Starts loading the page next to the cursor if the page layout wants a spread.
+    fn load_partner(&mut self, ctx: &context::AppCtx) {
+        if !self.config.mode.page_layout.is_spread() {
+            return;
+        }
+        if let (State::LoadingImage { .. }, Some(cur)) = (&self.state, self.cursor) {
+            let partner = self
+                .images
+                .next(cur)
+                .filter(|&partner| !self.failed.contains_key(partner));
+            if let Some(partner) = partner {
+                let path = self.images.get(partner).unwrap().to_owned();
+                self.spread = Some(Spread {
+                    partner,
+                    first: None,
+                    second: Page::Loading(ctx.load_image(partner, path)),
+                });
+            }
+        }
+    }
+
+    fn cancel_spread(&mut self) {
+        if let Some(Spread {
+            second: Page::Loading(abort_handle),
+            ..
+        }) = self.spread.take()
+        {
+            abort_handle.abort();
+        }
+    }
+
+    fn partner_failed(&mut self, main: &widgets::Main) {
+        if let Some(spread) = &mut self.spread {
+            spread.second = Page::Failed;
+        }
+        self.show_spread_if_ready(main);
+    }
+
+    /// Shows the spread once both pages are done loading, or the first page
+    /// alone if it's landscape or the second one failed.
+    fn show_spread_if_ready(&mut self, main: &widgets::Main) {
+        let spread = match &self.spread {
+            Some(spread) => spread,
+            None => return,
+        };
+        let first = match &spread.first {
+            Some(first) => first.clone(),
+            None => return,
+        };
+
+        let is_portrait = |img: &Pixbuf| img.get_width() <= img.get_height();
+        let img = match &spread.second {
+            Page::Loading(_) if !is_portrait(&first) => None,
+            Page::Loading(_) => return,
+            Page::Loaded(second) if is_portrait(&first) && is_portrait(second) => {
+                let rtl = self.config.mode.page_layout == config::PageLayout::SpreadRtl;
+                let (left, right) = if rtl {
+                    (second, &first)
+                } else {
+                    (&first, second)
+                };
+                join_pages(left, right, self.config.interpolation_algorithm)
+            }
+            Page::Loaded(_) | Page::Failed => None,
+        };
+
+        let img = match img {
+            Some(img) => img,
+            None => {
+                self.cancel_spread();
+                first
+            }
+        };
+        main.set_loading(false);
+        self.show_loaded(main, img);
+        self.update_info(main);
+    }
+
+    fn update_metadata_panel(&self, main: &widgets::Main) {
+        let tags = self
+            .cursor
+            .and_then(|cur| self.images_exif.get(cur))
+            .map(|exif| exif.tags.as_slice())
+            .unwrap_or(&[]);
+        main.set_metadata(tags);
+    }
+
+    fn retry_load(&mut self, ctx: &context::AppCtx, main: &widgets::Main) {
+        if let Some(cur) = self.cursor {
+            if self.failed.remove(cur).is_some() {
+                self.try_load(ctx, main, ImageTransition::Current);
+            }
+        }
+    }
+
+    fn toggle_slideshow(&mut self, main: &widgets::Main, tx: &glib::Sender<Event>) {
+        match self.slideshow.take() {
+            Some(slideshow) => glib::source_remove(slideshow.timer),
+            None => {
+                let tx = tx.clone();
+                let timer = glib::timeout_add_local(SLIDESHOW_TICK.as_millis() as u32, move || {
+                    let _ = tx.send(Event::SlideshowTick);
+                    Continue(true)
+                });
+                self.slideshow = Some(Slideshow {
+                    timer,
+                    remaining: self.config.slideshow_interval.0,
+                });
+            }
+        }
+        self.update_info(main);
+    }
+
+    /// Starts the slideshow interval over so every image is shown for all of it.
+    fn restart_slideshow_interval(&mut self) {
+        if let Some(slideshow) = &mut self.slideshow {
+            slideshow.remaining = self.config.slideshow_interval.0;
+        }
+    }
+
+    fn slideshow_tick(&mut self, ctx: &context::AppCtx, main: &widgets::Main) {
+        if let State::LoadingImage { .. } = self.state {
+            return;
+        }
+        let slideshow = match &mut self.slideshow {
+            Some(slideshow) => slideshow,
+            None => return,
+        };
+
+        let shown_secs = |remaining: Duration| remaining.as_secs_f64().ceil();
+        let before = shown_secs(slideshow.remaining);
+        slideshow.remaining = slideshow
+            .remaining
+            .checked_sub(SLIDESHOW_TICK)
+            .unwrap_or_default();
+        if slideshow.remaining > Duration::from_secs(0) {
+            if shown_secs(slideshow.remaining) != before {
+                self.update_info(main);
+            }
+            return;
+        }
+
+        if self.try_transition(ImageTransition::Next).is_some() {
+            self.try_load(ctx, main, ImageTransition::Next);
+        } else if self.config.slideshow_loop {
+            self.try_load(ctx, main, ImageTransition::Start);
+        } else if let Some(slideshow) = self.slideshow.take() {
+            glib::source_remove(slideshow.timer);
+            main.show_message(
+                "Slideshow reached the last image",
+                self.config.error_display_duration.0,
+            );
+            self.update_info(main);
+        }
+    }
+
+    /// Zoom level if it was set by hand.
+    fn manual_zoom(&self) -> Option<f64> {
+        match (&self.state, self.sticky_scaling) {
+            (State::DisplayImage { scale, .. }, None) => Some(*scale),
+            _ => None,
+        }
+    }
+
+    /// The archive at the cursor if its reading position is remembered.
+    fn current_archive(&self) -> Option<positions::Archive> {
+        if !self.remember_positions {
+            return None;
+        }
+        let path = Path::new(self.images.get(self.cursor?)?);
+        Some(path)
+            .filter(|path| is_archive(path))
+            .and_then(positions::Archive::of)
+    }
+
+    /// Remembers the zoom and scroll position of the archive at the cursor.
+    fn save_position(&self, main: &widgets::Main) {
+        if let State::DisplayImage { .. } = self.state {
+            if let Some(archive) = self.current_archive() {
+                let position = positions::Position {
+                    archive,
+                    zoom: self.manual_zoom(),
+                    scroll: main.scroll_position(),
+                };
+                if let Err(e) = positions::store(position) {
+                    log::error!("Can't save reading position: {}", e);
                 }
-            };
+            }
+        }
+    }
+
+    /// Shows the archive at the cursor like it was left off once it's loaded.
+    fn restore_position(&mut self) {
+        if !self.restore_positions {
+            return;
+        }
+        let loaded = self
+            .current_archive()
+            .map(|archive| positions::load(&archive));
+        let position = match loaded {
+            Some(Ok(Some(position))) => position,
+            Some(Err(e)) => {
+                log::error!("Can't read reading positions: {}", e);
+                return;
+            }
+            Some(Ok(None)) | None => return,
+        };
+        match position.zoom {
+            Some(_) => self.sticky_scaling = None,
+            None if self.sticky_scaling.is_none() => {
+                self.sticky_scaling = Some(self.config.mode.initial_scaling)
+            }
+            None => {}
+        }
+        self.restore_view = Some(View {
+            scale: position.zoom.unwrap_or(1.),
+            scroll: position.scroll,
+        });
+    }
+
+    fn session(&self, main: &widgets::Main) -> session::Session {
+        session::Session {
+            mode: self.config.viewer_mode,
+            images: iter_keys(&self.images)
+                .filter_map(|key| self.images.get(key).cloned())
+                .collect(),
+            index: self.index.unwrap_or(0),
+            rotation: self.rotation,
+            zoom: self.manual_zoom(),
+            scroll: main.scroll_position(),
+        }
+    }
+
+    /// Keeps the last session up to date so it survives crashes.
+    fn autosave_session(&self, ctx: &context::AppCtx, main: &widgets::Main) {
+        if self.images.head().is_some() {
+            ctx.autosave(self.session(main));
+        }
+    }
+
+    /// Saves the session as `name` or under a name made from the current time if it's empty.
+    fn save_named_session(&self, main: &widgets::Main, name: &str) {
+        let name = if name.is_empty() {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or(0);
+            format!("saved-{}", secs)
+        } else {
+            name.to_owned()
+        };
+        let duration = self.config.error_display_duration.0;
+        match session::save(&name, &self.session(main)) {
+            Ok(_) => main.show_message(&format!("Saved session {}", name), duration),
+            Err(e) => main.show_error(&format!("Can't save session: {}", e), duration),
+        }
+    }
+
+    fn print_failed_summary(&self) {
+        let kept = self.failed.iter().filter_map(|(id, err)| {
+            self.images
+                .get(id)
+                .map(|path| (path.as_str(), err.as_str()))
+        });
+        let skipped = self
+            .skipped
+            .iter()
+            .map(|(path, err)| (path.as_str(), err.as_str()));
+        let mut failed = kept.chain(skipped).peekable();
+        if failed.peek().is_some() {
+            eprintln!("Failed loading:");
+            for (path, err) in failed {
+                eprintln!("  {}: {}", path, err);
+            }
+        }
+    }
+
+    fn zoom_in(&mut self, main: &widgets::Main) {
+        if let State::DisplayImage { scale, .. } = self.state {
+            self.sticky_scaling = None;
+            let next = math::step_next(scale, self.config.zoom_step_size.0);
+            self.zoom_to(main, next);
         }
     }
 
     fn zoom_out(&mut self, main: &widgets::Main) {
-        if let State::DisplayImage { img, scale } = &self.state {
-            self.state = {
-                let step_size = self.config.zoom_step_size.0;
-                let next = f64::max(math::step_prev(*scale, step_size), step_size);
-                let img_px: euclid::Vector2D<_, Pixels> = vec2(img.get_width(), img.get_height());
-                let scaled = (img_px.to_f64() * next).cast();
-                let resized = img
-                    .scale_simple(scaled.x, scaled.y, self.config.interpolation_algorithm)
-                    .unwrap();
-                main.set_image(Some(&resized));
-                State::DisplayImage {
-                    img: img.clone(),
-                    scale: next,
+        if let State::DisplayImage { scale, .. } = self.state {
+            self.sticky_scaling = None;
+            let step_size = self.config.zoom_step_size.0;
+            let next = f64::max(math::step_prev(scale, step_size), step_size);
+            self.zoom_to(main, next);
+        }
+    }
+
+    fn zoom_to(&mut self, main: &widgets::Main, next: f64) {
+        let interp = self.config.interpolation_algorithm;
+        if let State::DisplayImage { img, scale } = &mut self.state {
+            let img_px: Vector2D<_, Pixels> = vec2(img.get_width(), img.get_height());
+            let resized = (img_px.to_f64() * next)
+                .try_cast()
+                .and_then(|scaled| img.scale_simple(scaled.x, scaled.y, interp));
+            match resized {
+                Some(resized) => {
+                    main.set_image(Some(&resized));
+                    *scale = next;
                 }
-            };
+                None => log::warn!("Can't zoom to {}%", next * 100.),
+            }
         }
     }
 
@@ -394,31 +1394,306 @@ impl App {
     where
         F: Fn(Vector2D<i32, Pixels>, Vector2D<i32, Pixels>) -> Option<(Vector2D<i32, Pixels>, f64)>,
     {
-        if let State::DisplayImage { img, .. } = &self.state {
+        let interp = self.config.interpolation_algorithm;
+        if let State::DisplayImage { img, scale } = &mut self.state {
             let alloc = main.image_allocation();
             let img_px = vec2(img.get_width(), img.get_height());
 
-            let (scaled, scale) = f(alloc, img_px).unwrap();
+            // a zero sized window or zoom leaves nothing to scale to
+            let resized = f(alloc, img_px).and_then(|(scaled, next)| {
+                img.scale_simple(scaled.x, scaled.y, interp)
+                    .map(|resized| (resized, next))
+            });
 
-            let resized = img
-                .scale_simple(scaled.x, scaled.y, self.config.interpolation_algorithm)
-                .unwrap();
+            match resized {
+                Some((resized, next)) => {
+                    main.set_image(Some(&resized));
+                    *scale = next;
+                }
+                None => log::warn!("Can't scale {:?} into {:?}", img_px, alloc),
+            }
+        }
+    }
 
-            main.set_image(Some(&resized));
-            self.state = State::DisplayImage {
-                img: img.clone(),
-                scale,
-            };
+    /// Displays a freshly loaded image either from scratch or like the previous one.
+    fn show_loaded(&mut self, main: &widgets::Main, img: Pixbuf) {
+        let img = rotate(&img, self.rotation).unwrap_or(img);
+        let last_view = self.last_view.take().filter(|_| self.keep_view);
+        match self.restore_view.take().or(last_view) {
+            Some(view) => {
+                self.state = State::DisplayImage {
+                    img,
+                    scale: view.scale,
+                };
+                self.rescale(main);
+                main.set_scroll_position(view.scroll);
+            }
+            None => {
+                self.state = State::DisplayImage { img, scale: 100. };
+                self.scale_initial(main);
+            }
+        }
+        if let Some(scroll) = self.pending_scroll.take() {
+            main.scroll_when_allocated(Scroll::V(scroll));
         }
     }
 
-    fn scale_initial(&mut self, main: &widgets::Main) {
-        let scaling = self.config.mode.initial_scaling;
+    /// Shows the next or previous image when scrolling beyond the current one,
+    /// returns whether it did.
+    fn scroll_past_end(
+        &mut self,
+        ctx: &context::AppCtx,
+        main: &widgets::Main,
+        direction: ScrollV,
+    ) -> bool {
+        if !self.config.mode.advance_on_scroll_end || self.strip.is_some() {
+            return false;
+        }
+        let (transition, position) = match direction {
+            ScrollV::Down if main.at_scroll_edge(direction) => {
+                (ImageTransition::Next, ScrollV::Start)
+            }
+            ScrollV::Up if main.at_scroll_edge(direction) => {
+                (ImageTransition::Prev, ScrollV::End)
+            }
+            _ => return false,
+        };
+        if self.try_transition(transition).is_none() {
+            return false;
+        }
+
+        self.try_load(ctx, main, transition);
+        self.pending_scroll = Some(position);
+        true
+    }
+
+    /// Scales with `scaling` and keeps applying it when the image area is resized.
+    fn scale_sticky(&mut self, main: &widgets::Main, scaling: config::ImageScaling) {
+        self.sticky_scaling = Some(scaling);
         self.scale(main, |a, b| math::scale(a, b, scaling))
     }
 
+    /// Loads pages around the viewport and moves the cursor to the page under its centre.
+    fn strip_scrolled(&mut self, ctx: &context::AppCtx, main: &widgets::Main) {
+        let strip = match &mut self.strip {
+            Some(strip) => strip,
+            None => return,
+        };
+        let (visible, current) = match main.strip_viewport() {
+            Some(viewport) => viewport,
+            None => return,
+        };
+
+        let window = visible.start.saturating_sub(STRIP_KEEP)
+            ..usize::min(visible.end + STRIP_KEEP, strip.pages.len());
+        for pos in strip.window.clone().filter(|pos| !window.contains(pos)) {
+            let page = strip.pages[pos];
+            if strip.originals.remove(page).is_some() {
+                main.clear_strip_page(pos);
+            }
+            if let Some(handle) = strip.loading.remove(page) {
+                handle.abort();
+            }
+        }
+        for &page in &strip.pages[window.clone()] {
+            if !strip.originals.contains_key(page)
+                && !strip.loading.contains_key(page)
+                && !self.failed.contains_key(page)
+            {
+                let path = self.images.get(page).unwrap().to_owned();
+                strip.loading.insert(page, ctx.load_image(page, path));
+            }
+        }
+        strip.window = window;
+        main.set_loading(!strip.loading.is_empty());
+
+        let cursor = strip.pages.get(current).copied();
+        if cursor.is_some() && cursor != self.cursor {
+            self.cursor = cursor;
+            self.index = Some(current);
+            self.restart_slideshow_interval();
+            self.update_metadata_panel(main);
+            self.update_info(main);
+        }
+    }
+
+    fn strip_position(&self, id: DefaultKey) -> Option<usize> {
+        self.strip
+            .as_ref()
+            .and_then(|strip| strip.pages.iter().position(|&page| page == id))
+    }
+
+    /// Size of a page with `img_px` pixels scaled to the width of the image area.
+    fn strip_page_size(
+        &self,
+        main: &widgets::Main,
+        img_px: Vector2D<i32, Pixels>,
+    ) -> Option<Vector2D<i32, Pixels>> {
+        let alloc = main.image_allocation();
+        math::scale(alloc, img_px, config::ImageScaling::FitToWidth).map(|(scaled, _)| scaled)
+    }
+
+    /// The page scaled to the width of the image area.
+    fn strip_scaled(&self, main: &widgets::Main, img: &Pixbuf) -> Option<Pixbuf> {
+        let scaled = self.strip_page_size(main, vec2(img.get_width(), img.get_height()))?;
+        img.scale_simple(scaled.x, scaled.y, self.config.interpolation_algorithm)
+    }
+
+    fn strip_loaded(&mut self, main: &widgets::Main, id: DefaultKey, img: Pixbuf) {
+        let pos = match self.strip_position(id) {
+            Some(pos) => pos,
+            None => return,
+        };
+        if let Some(strip) = &mut self.strip {
+            strip.loading.remove(id);
+            main.set_loading(!strip.loading.is_empty());
+            // scrolled away while it was loading
+            if !strip.window.contains(&pos) {
+                return;
+            }
+        }
+        if let Some(scaled) = self.strip_scaled(main, &img) {
+            main.set_strip_page(pos, &scaled);
+        }
+        if let Some(strip) = &mut self.strip {
+            strip.originals.insert(id, img);
+        }
+        if self.cursor == Some(id) {
+            self.update_info(main);
+        }
+    }
+
+    fn strip_failed(&mut self, main: &widgets::Main, id: DefaultKey, err: String) {
+        if let Some(strip) = &mut self.strip {
+            strip.loading.remove(id);
+            main.set_loading(!strip.loading.is_empty());
+        }
+        if let Some(pos) = self.strip_position(id) {
+            main.set_strip_page_broken(pos);
+        }
+        if let Some(path) = self.images.get(id) {
+            log::error!("Failed loading image {}: {}", path, err);
+            main.show_error(
+                &format!("Failed loading {}: {}", path, err),
+                self.config.error_display_duration.0,
+            );
+        }
+        self.failed.insert(id, err);
+    }
+
+    /// Scales the loaded pages to the current width, resizes the placeholders of the other
+    /// pages with known dimensions and scrolls to the start page once.
+    fn strip_rescale(&mut self, main: &widgets::Main) {
+        let strip = match &self.strip {
+            Some(strip) => strip,
+            None => return,
+        };
+        for (pos, &page) in strip.pages.iter().enumerate() {
+            if let Some(img) = strip.originals.get(page) {
+                if let Some(scaled) = self.strip_scaled(main, img) {
+                    main.set_strip_page(pos, &scaled);
+                }
+            } else if let Some(size) = self
+                .images_meta
+                .get(page)
+                .and_then(|meta| self.strip_page_size(main, meta.dimensions))
+            {
+                main.set_strip_page_height(pos, size.y);
+            }
+        }
+
+        if let Some(strip) = &mut self.strip {
+            if !std::mem::replace(&mut strip.positioned, true) {
+                main.scroll_to_strip_page(self.index.unwrap_or(0));
+            }
+        }
+    }
+
+    /// Scales the image again with the sticky scaling or the current zoom level.
+    fn rescale(&mut self, main: &widgets::Main) {
+        match (self.sticky_scaling, &self.state) {
+            (Some(scaling), _) => self.scale_sticky(main, scaling),
+            (None, State::DisplayImage { scale, .. }) => {
+                let scale = *scale;
+                self.scale(main, |_, b| {
+                    (b.to_f64() * scale).floor().try_cast().map(|r| (r, scale))
+                })
+            }
+            (None, _) => {}
+        }
+    }
+
+    fn scale_initial(&mut self, main: &widgets::Main) {
+        self.scale_sticky(main, self.config.mode.initial_scaling)
+    }
+
     fn scale_to_fit(&mut self, main: &widgets::Main) {
-        self.scale(main, math::scale_to_fit)
+        self.scale_sticky(main, config::ImageScaling::Fit)
+    }
+
+    fn rotate(&mut self, main: &widgets::Main, degrees: u32) {
+        self.rotation = (self.rotation + degrees) % 360;
+        if let State::DisplayImage { img, scale } = &self.state {
+            if let Some(rotated) = rotate(img, degrees) {
+                self.state = State::DisplayImage {
+                    img: rotated,
+                    scale: *scale,
+                };
+                self.rescale(main);
+            }
+        }
+        self.update_info(main);
+    }
+
+    /// Reloads the config files, keeping the old config if the new one is broken.
+    fn reload_config(&mut self, ctx: &context::AppCtx, main: &widgets::Main) {
+        let load_options = config::LoadOptions {
+            write_default: false,
+            ..self.load_options.clone()
+        };
+        let result = config::UserConfig::load(&load_options)
+            .map_err(|e| e.to_string())
+            .and_then(|config| {
+                config
+                    .split_for_app_use(self.config.viewer_mode)
+                    .map_err(|e| e.to_string())
+            });
+
+        match result {
+            Ok((keymap, mut config)) => {
+                self.overrides.apply(&mut config);
+                let wants_strip = config.mode.page_layout == config::PageLayout::Strip;
+                if wants_strip != self.strip.is_some() {
+                    main.show_message(
+                        "Switching page-layout to or from strip needs a restart",
+                        config.error_display_duration.0,
+                    );
+                    config.mode.page_layout = self.config.mode.page_layout;
+                }
+                if config.mode.new_image_view != self.config.mode.new_image_view {
+                    self.keep_view = config.mode.new_image_view == config::NewImageView::Keep;
+                }
+                let rescale = config.mode.initial_scaling != self.config.mode.initial_scaling
+                    || config.interpolation_algorithm != self.config.interpolation_algorithm;
+                main.set_help(&events::bindings(&keymap));
+                *self.keymap.borrow_mut() = keymap;
+                self.config = config;
+                if rescale {
+                    self.scale_initial(main);
+                }
+                self.update_info(main);
+                // includes might have been added or removed
+                self.config_monitors = ctx.watch_files(&config::watched_paths(&self.load_options));
+                log::info!("Reloaded config");
+            }
+            Err(e) => {
+                log::error!("Can't reload config: {}", e);
+                main.show_error(
+                    &format!("Can't reload config: {}", e),
+                    self.config.error_display_duration.0,
+                );
+            }
+        }
     }
 
     fn toggle_fullscreen(&mut self, window: &gtk::Window) {
@@ -442,22 +1717,149 @@ enum State {
         img: Pixbuf,
         scale: f64,
     },
+    /// The current image failed loading and is kept as a placeholder.
+    Broken,
+    /// Pages are shown in a strip and loaded as they're scrolled into view.
+    Strip,
+}
+
+/// How many pages `Prev` goes back to the start of the previous spread, given
+/// whether each page before the cursor, nearest first, can be paired.
+/// Reading forward pairs up every run of pairable pages from its start, so the
+/// page before the cursor closes a spread if its run has an even length.
+fn spread_steps_back(pairable: impl IntoIterator<Item = bool>) -> usize {
+    let run = pairable
+        .into_iter()
+        .take_while(|&pairable| pairable)
+        .count();
+    if run > 0 && run % 2 == 0 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Puts `left` and `right` side by side, scaling `right` to the height of `left`.
+fn join_pages(left: &Pixbuf, right: &Pixbuf, interp: InterpType) -> Option<Pixbuf> {
+    let height = left.get_height();
+    let right = if right.get_height() == height {
+        right.clone()
+    } else {
+        let scale = f64::from(height) / f64::from(right.get_height());
+        let width = (f64::from(right.get_width()) * scale).round() as i32;
+        right.scale_simple(width, height, interp)?
+    };
+
+    let has_alpha = left.get_has_alpha() || right.get_has_alpha();
+    let joined = Pixbuf::new(
+        Colorspace::Rgb,
+        has_alpha,
+        8,
+        left.get_width() + right.get_width(),
+        height,
+    )?;
+    joined.fill(if has_alpha { 0 } else { 0xff });
+    left.copy_area(0, 0, left.get_width(), height, &joined, 0, 0);
+    right.copy_area(0, 0, right.get_width(), height, &joined, left.get_width(), 0);
+    Some(joined)
 }
 
-fn default_format_map() -> FormatMap {
-    let mut ret = FormatMap::new();
-    ret.insert("index", -1.0);
-    ret.insert("nimages", -1.0);
-    ret.insert("width", -1.0);
-    ret.insert("height", -1.0);
-    ret.insert("filesize", -1.0);
-    ret.insert("filename", "".to_string());
-    ret
+fn print_actions(keymap: Option<&HashMap<KeyPress, events::UserEvent>>) {
+    let bindings = keymap.map(events::bindings);
+    let name_width = events::UserEvent::ALL
+        .iter()
+        .map(|action| action.name().len())
+        .max()
+        .unwrap_or(0);
+
+    match bindings {
+        Some(bindings) => {
+            let keys: Vec<_> = bindings.iter().map(|(_, keys)| keys.join(", ")).collect();
+            let keys_width = keys.iter().map(|keys| keys.len()).max().unwrap_or(0);
+            for ((action, _), keys) in bindings.iter().zip(&keys) {
+                println!(
+                    "{:name_width$}  {:keys_width$}  {}",
+                    action.name(),
+                    keys,
+                    action.description(),
+                    name_width = name_width,
+                    keys_width = keys_width,
+                );
+            }
+        }
+        None => {
+            for action in events::UserEvent::ALL {
+                println!(
+                    "{:name_width$}  {}",
+                    action.name(),
+                    action.description(),
+                    name_width = name_width,
+                );
+            }
+        }
+    }
 }
 
 fn run() -> Result<(), Error> {
+    let mut opt = Opt::from_args();
+    if opt.list_actions {
+        print_actions(None);
+        return Ok(());
+    }
+
+    if opt.dump_keymap {
+        let config = config::UserConfig::load(&opt.load_options()).context(ReadConfig)?;
+        let (keymap, _) = config
+            .split_for_app_use(config::ViewerMode::Image)
+            .context(Format)?;
+        print_actions(Some(&keymap));
+        return Ok(());
+    }
+
+    if opt.check_config {
+        let diagnostics = config::check_config(&opt.load_options());
+        for diagnostic in &diagnostics {
+            println!("{}", diagnostic);
+        }
+        return if diagnostics.is_empty() {
+            println!("Config OK");
+            Ok(())
+        } else {
+            Err(Error::CheckConfig {
+                problems: diagnostics.len(),
+            })
+        };
+    }
+
+    if let Some(Command::Info {
+        json,
+        format,
+        images,
+    }) = opt.cmd.take()
+    {
+        // a read-only command shouldn't leave a config file behind
+        let load_options = config::LoadOptions {
+            write_default: false,
+            ..opt.load_options()
+        };
+        let config = config::UserConfig::load(&load_options).context(ReadConfig)?;
+        let (_, config) = config
+            .split_for_app_use(config::ViewerMode::Image)
+            .context(Format)?;
+        let format = match format {
+            Some(format) => Template::parse(&format, config::FORMAT_KEYS).context(FormatArg)?,
+            None => config.status_format,
+        };
+        let output = if json {
+            info::Output::Json
+        } else {
+            info::Output::Format(format)
+        };
+        return info::run(&images, &output).context(Info);
+    }
+
     gtk::init().map_err(|_| Error::InitGtk)?;
-    if let Err(e) = gtk_run() {
+    if let Err(e) = gtk_run(opt) {
         gtk::MessageDialog::new(
             None::<&gtk::Window>,
             gtk::DialogFlags::empty(),
@@ -475,19 +1877,122 @@ fn run() -> Result<(), Error> {
 
 #[derive(StructOpt, Debug)]
 struct Opt {
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+
+    /// Only read this config file instead of the system and user configs
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Don't write the default config if there's no user config
+    #[structopt(long)]
+    no_write_default: bool,
+
+    /// Check the config files for problems and exit
+    #[structopt(long)]
+    check_config: bool,
+
+    /// List every action that can be bound in the keymap and exit
+    #[structopt(long)]
+    list_actions: bool,
+
+    /// Print the keys bound to each action after reading the config and exit
+    #[structopt(long)]
+    dump_keymap: bool,
+
+    /// Use the settings for `image` or `archive` instead of guessing from the file names
+    #[structopt(long, parse(try_from_str = config::parse_arg))]
+    mode: Option<config::ViewerMode>,
+
+    /// Initial scaling like fit, fit-to-width, shrink-to-fit, fill or `zoom: 150%`
+    #[structopt(long, parse(try_from_str = config::parse_arg))]
+    scaling: Option<config::ImageScaling>,
+
+    /// Initial window size like 1280x800
+    #[structopt(long, parse(try_from_str = config::parse_arg))]
+    geometry: Option<config::Ratio>,
+
+    /// Initial window size relative to the monitor like 50%
+    #[structopt(long, parse(try_from_str = config::parse_arg))]
+    scale: Option<config::Percent>,
+
+    /// Start in fullscreen
+    #[structopt(long)]
+    fullscreen: bool,
+
+    /// Start at the image with this 1-based index
+    #[structopt(long)]
+    start_at: Option<usize>,
+
+    /// Show archives from the top instead of where they were left off
+    #[structopt(long)]
+    from_start: bool,
+
+    /// Reopen the last session or the one saved as NAME
+    #[structopt(long, value_name = "NAME", require_equals = true)]
+    restore: Option<Option<String>>,
+
     images: Vec<String>,
 }
 
+impl Opt {
+    fn load_options(&self) -> config::LoadOptions {
+        config::LoadOptions {
+            path: self.config.clone(),
+            write_default: !self.no_write_default,
+        }
+    }
+
+    fn overrides(&self) -> config::Overrides {
+        config::Overrides {
+            scaling: self.scaling,
+            scale: self.scale,
+            window_size: self.geometry,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Print information about images without opening a window
+    Info {
+        /// Print one JSON object per image instead of using status-format
+        #[structopt(long)]
+        json: bool,
+
+        /// Format string to use instead of status-format
+        #[structopt(long)]
+        format: Option<String>,
+
+        images: Vec<String>,
+    },
+}
+
 #[derive(Snafu, Debug)]
 enum Error {
     #[snafu(display("Can't init gtk"))]
     InitGtk,
 
     #[snafu(display("Can't read config: {}", source))]
-    ReadConfig { source: cfgen::Error },
+    ReadConfig { source: config::LoadError },
+
+    #[snafu(display("{}", source))]
+    Format { source: config::FormatError },
+
+    #[snafu(display("Bad --format: {}", source))]
+    FormatArg { source: template::Error },
+
+    #[snafu(display("Bad status-bar.css in config: {}", source))]
+    Css { source: glib::Error },
 
-    #[snafu(display("Bad status_format in config: {}", source))]
-    Format { source: formatter::Error },
+    #[snafu(display("{}", source))]
+    Info { source: info::Error },
+
+    #[snafu(display("Found {} problem(s) in config", problems))]
+    CheckConfig { problems: usize },
+
+    #[snafu(display("Can't restore session: {}", source))]
+    RestoreSession { source: session::Error },
 }
 
 fn main() {
@@ -497,3 +2002,59 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P: bool = true;
+    const L: bool = false;
+
+    /// First page of every spread when reading `pages` from the start, where
+    /// two portrait pages in a row are shown together.
+    fn spread_starts(pages: &[bool]) -> Vec<usize> {
+        let mut starts = Vec::new();
+        let mut page = 0;
+        while page < pages.len() {
+            starts.push(page);
+            let paired = pages[page] && pages.get(page + 1) == Some(&P);
+            page += if paired { 2 } else { 1 };
+        }
+        starts
+    }
+
+    #[test]
+    fn stepping_back_retraces_spreads() {
+        let books: &[&[bool]] = &[
+            &[P, P, P, P, P],
+            &[P, L, P, P],
+            &[L, P, P, P],
+            &[P, P, P, L, P, P],
+            &[P, L, L, P, P, P, L, P],
+            &[L, L, L],
+            &[P, P, L, P, L, P, P, P, P],
+        ];
+        for pages in books {
+            let starts = spread_starts(pages);
+            for spreads in starts.windows(2) {
+                let (prev, cur) = (spreads[0], spreads[1]);
+                let before = pages[..cur].iter().rev().copied();
+                assert_eq!(
+                    cur - spread_steps_back(before),
+                    prev,
+                    "{:?} at {}",
+                    pages,
+                    cur
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn landscape_page_before_is_shown_alone() {
+        assert_eq!(spread_steps_back(vec![L, P]), 1);
+        assert_eq!(spread_steps_back(vec![P, L]), 1);
+        assert_eq!(spread_steps_back(vec![P, P, L]), 2);
+        assert_eq!(spread_steps_back(vec![P, P, P]), 1);
+    }
+}
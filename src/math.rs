@@ -3,7 +3,7 @@ use std::f64;
 use euclid::Vector2D;
 use num_traits::NumCast;
 
-use crate::config::ImageScaling;
+use crate::config::{ImageScaling, Percent};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Pixels;
@@ -22,6 +22,10 @@ where
         ImageScaling::Fit => f64::min(a.x / b.x, a.y / b.y),
         ImageScaling::FitToWidth => a.x / b.x,
         ImageScaling::FitToHeight => a.y / b.y,
+        ImageScaling::ShrinkToFit => f64::min(1.0, f64::min(a.x / b.x, a.y / b.y)),
+        ImageScaling::Fill => f64::max(a.x / b.x, a.y / b.y),
+        ImageScaling::FitToWidthShrinkOnly => f64::min(1.0, a.x / b.x),
+        ImageScaling::Zoom(Percent(s)) => s,
     };
 
     (b * s).floor().try_cast().map(|r| (r, s))
@@ -51,3 +55,52 @@ pub fn step_prev(orig: f64, step_size: f64) -> f64 {
 pub fn step_next(orig: f64, step_size: f64) -> f64 {
     step_with(|a, b| a + b)(orig, step_size)
 }
+
+#[cfg(test)]
+mod tests {
+    use euclid::vec2;
+
+    use super::*;
+
+    const WINDOW: (i32, i32) = (800, 600);
+    const LARGE: (i32, i32) = (3200, 1200);
+    const SMALL: (i32, i32) = (200, 300);
+
+    fn scaled(image: (i32, i32), scaling: ImageScaling) -> ((i32, i32), f64) {
+        let window = vec2(WINDOW.0, WINDOW.1);
+        let (size, factor) = scale(window, vec2(image.0, image.1), scaling).unwrap();
+        ((size.x, size.y), factor)
+    }
+
+    #[test]
+    fn fit_scales_both_ways() {
+        assert_eq!(scaled(LARGE, ImageScaling::Fit), ((800, 300), 0.25));
+        assert_eq!(scaled(SMALL, ImageScaling::Fit), ((400, 600), 2.));
+    }
+
+    #[test]
+    fn shrink_to_fit_never_scales_up() {
+        assert_eq!(scaled(LARGE, ImageScaling::ShrinkToFit), ((800, 300), 0.25));
+        assert_eq!(scaled(SMALL, ImageScaling::ShrinkToFit), ((200, 300), 1.));
+    }
+
+    #[test]
+    fn fill_covers_the_window() {
+        assert_eq!(scaled(LARGE, ImageScaling::Fill), ((1600, 600), 0.5));
+        assert_eq!(scaled(SMALL, ImageScaling::Fill), ((800, 1200), 4.));
+    }
+
+    #[test]
+    fn fit_to_width_shrink_only_never_scales_up() {
+        let scaling = ImageScaling::FitToWidthShrinkOnly;
+        assert_eq!(scaled(LARGE, scaling), ((800, 300), 0.25));
+        assert_eq!(scaled(SMALL, scaling), ((200, 300), 1.));
+    }
+
+    #[test]
+    fn zoom_ignores_the_window() {
+        let scaling = ImageScaling::Zoom(Percent(1.5));
+        assert_eq!(scaled(LARGE, scaling), ((4800, 1800), 1.5));
+        assert_eq!(scaled(SMALL, scaling), ((300, 450), 1.5));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fs, io::Cursor, path::Path};
+
+use exif::{Exif, Field, In, Tag};
+use snafu::{ResultExt, Snafu};
+
+const XMP_START: &[u8] = b"<x:xmpmeta";
+const XMP_END: &[u8] = b"</x:xmpmeta>";
+
+/// EXIF and XMP metadata of an image.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// Every EXIF tag of the primary image and every XMP property as name and
+    /// human readable value.
+    pub tags: Vec<(String, String)>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub exposure: Option<String>,
+    pub iso: Option<String>,
+    pub focal_length: Option<String>,
+    pub date: Option<String>,
+    pub gps: Option<String>,
+}
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("Can't open file: {}", source))]
+    Open { source: std::io::Error },
+
+    #[snafu(display("Can't read exif: {}", source))]
+    Read { source: exif::Error },
+}
+
+fn display(exif: &Exif, field: &Field) -> String {
+    match &field.value {
+        // the default display quotes strings
+        exif::Value::Ascii(strings) => strings
+            .iter()
+            .map(|s| String::from_utf8_lossy(s).trim().to_owned())
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => field.display_value().with_unit(exif).to_string(),
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The XMP packet in `data`, JPEG, PNG and TIFF all embed it as plain text.
+fn xmp_packet(data: &[u8]) -> Option<&str> {
+    let start = find(data, XMP_START)?;
+    let len = find(&data[start..], XMP_END)? + XMP_END.len();
+    std::str::from_utf8(&data[start..start + len]).ok()
+}
+
+/// Whether `name` is a property and not part of the RDF structure or a namespace.
+fn is_property(name: &str) -> bool {
+    let mut parts = name.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(prefix), Some(_)) => !["x", "rdf", "xml", "xmlns"].contains(&prefix),
+        _ => false,
+    }
+}
+
+/// `name="value"` pairs in the inside of a tag.
+fn attributes(mut s: &str) -> Vec<(&str, &str)> {
+    let mut ret = Vec::new();
+    while let Some(eq) = s.find('=') {
+        let name = s[..eq].trim();
+        let value = s[eq + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        ret.push((name, &value[1..end]));
+        s = &value[end + 1..];
+    }
+    ret
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Properties of an XMP packet given as attributes or elements, the items of
+/// arrays are joined.
+fn xmp_tags(packet: &str) -> Vec<(String, String)> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut add = |name: &str, value: &str| {
+        let value = unescape(value.trim());
+        if value.is_empty() {
+            return;
+        }
+        match tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, values)) => {
+                values.push_str(", ");
+                values.push_str(&value);
+            }
+            None => tags.push((name.to_owned(), value)),
+        }
+    };
+
+    let mut open: Vec<&str> = Vec::new();
+    let mut rest = packet;
+    while let Some(start) = rest.find('<') {
+        // text belongs to the innermost property, rdf:li items included
+        if let Some(property) = open.iter().rev().find(|name| is_property(name)) {
+            add(property, &rest[..start]);
+        }
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if tag.starts_with('/') {
+            open.pop();
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let mut parts = tag.trim_end_matches('/').splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        for (attr, value) in attributes(parts.next().unwrap_or("")) {
+            if is_property(attr) {
+                add(attr, value);
+            }
+        }
+        if !self_closing {
+            open.push(name);
+        }
+    }
+    tags
+}
+
+pub fn read(path: &Path) -> Result<Metadata, Error> {
+    let data = fs::read(path).context(Open)?;
+    let xmp = xmp_packet(&data).map(xmp_tags).unwrap_or_default();
+    let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(&data)) {
+        Ok(exif) => exif,
+        Err(_) if !xmp.is_empty() => {
+            return Ok(Metadata {
+                tags: xmp,
+                ..Metadata::default()
+            })
+        }
+        Err(e) => return Err(e).context(Read),
+    };
+
+    let get = |tag| {
+        exif.get_field(tag, In::PRIMARY)
+            .map(|field| display(&exif, field))
+    };
+
+    let gps = match (
+        get(Tag::GPSLatitude),
+        get(Tag::GPSLatitudeRef),
+        get(Tag::GPSLongitude),
+        get(Tag::GPSLongitudeRef),
+    ) {
+        (Some(lat), Some(lat_ref), Some(lon), Some(lon_ref)) => {
+            Some(format!("{} {}, {} {}", lat, lat_ref, lon, lon_ref))
+        }
+        _ => None,
+    };
+
+    Ok(Metadata {
+        tags: exif
+            .fields()
+            .filter(|field| field.ifd_num == In::PRIMARY)
+            .map(|field| (field.tag.to_string(), display(&exif, field)))
+            .chain(xmp)
+            .collect(),
+        make: get(Tag::Make),
+        model: get(Tag::Model),
+        exposure: get(Tag::ExposureTime),
+        iso: get(Tag::PhotographicSensitivity),
+        focal_length: get(Tag::FocalLength),
+        date: get(Tag::DateTimeOriginal),
+        gps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="XMP Core">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/" xmp:Rating="4"
+    xmp:Label='Red &amp; blue'>
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>beach</rdf:li>
+     <rdf:li>holiday</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <dc:title>
+    <rdf:Alt>
+     <rdf:li xml:lang="x-default">Sunset</rdf:li>
+    </rdf:Alt>
+   </dc:title>
+   <xmp:CreatorTool>Darktable</xmp:CreatorTool>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn finds_the_packet_between_binary_data() {
+        let mut data = vec![0xff, 0xd8, 0xff, 0xe1];
+        data.extend_from_slice(PACKET.as_bytes());
+        data.extend_from_slice(&[0xff, 0xd9]);
+        let packet = xmp_packet(&data).unwrap();
+        assert!(packet.starts_with("<x:xmpmeta"));
+        assert!(packet.ends_with("</x:xmpmeta>"));
+        assert_eq!(xmp_packet(&[0xff, 0xd8, 0xff, 0xd9]), None);
+    }
+
+    #[test]
+    fn reads_attributes_elements_and_arrays() {
+        let tags = xmp_tags(PACKET);
+        let tags: Vec<_> = tags
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                ("xmp:Rating", "4"),
+                ("xmp:Label", "Red & blue"),
+                ("dc:subject", "beach, holiday"),
+                ("dc:title", "Sunset"),
+                ("xmp:CreatorTool", "Darktable"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Zoom and scroll position of each archive, stored under `$XDG_STATE_HOME/iv`.
+use std::{fs, path::Path, time::UNIX_EPOCH};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::state::{self, Error};
+
+const POSITIONS_FILE: &str = "positions.json";
+
+/// Identifies an archive file, a changed size or mtime means the position is stale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Archive {
+    pub path: String,
+    pub size: u64,
+    pub mtime: u64,
+}
+
+impl Archive {
+    /// The archive file at `path`.
+    pub fn of(path: &Path) -> Option<Self> {
+        let path = path.canonicalize().ok()?;
+        let meta = fs::metadata(&path).ok()?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs())
+            .unwrap_or(0);
+
+        Some(Self {
+            path: path.to_string_lossy().into_owned(),
+            size: meta.len(),
+            mtime,
+        })
+    }
+}
+
+/// Where an archive was left off, saved when moving to another image and on quit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Position {
+    pub archive: Archive,
+    /// Zoom level if it was set by hand instead of by a scaling mode.
+    pub zoom: Option<f64>,
+    pub scroll: (f64, f64),
+}
+
+/// The remembered position in `archive` unless the archive changed since.
+pub fn load(archive: &Archive) -> Result<Option<Position>, Error> {
+    let db: Option<HashMap<String, Position>> = state::read(&state::path(POSITIONS_FILE)?)?;
+    Ok(db
+        .and_then(|mut db| db.remove(&archive.path))
+        .filter(|position| position.archive == *archive))
+}
+
+/// Remembers `position`, replacing the one of the same archive.
+pub fn store(position: Position) -> Result<(), Error> {
+    let path = state::path(POSITIONS_FILE)?;
+    // read again so positions stored by other instances are kept
+    let mut db: HashMap<String, Position> = state::read(&path)?.unwrap_or_default();
+    db.insert(position.archive.path.clone(), position);
+    state::write(&path, &db)
+}
@@ -0,0 +1,60 @@
+//! Image lists and view state saved under `$XDG_STATE_HOME/iv/sessions`.
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+use crate::{config::ViewerMode, state};
+
+const SESSIONS_DIR: &str = "sessions";
+/// Session that gets saved automatically while viewing.
+pub const LAST: &str = "last";
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("Bad session name `{}`", name))]
+    BadName { name: String },
+
+    #[snafu(display("No session named `{}`", name))]
+    NotFound { name: String },
+
+    #[snafu(display("{}", source))]
+    State { source: state::Error },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub mode: ViewerMode,
+    pub images: Vec<String>,
+    /// 0-based index of the current image.
+    pub index: usize,
+    pub rotation: u32,
+    /// Zoom level of the current image, `None` if a scaling mode picked it.
+    pub zoom: Option<f64>,
+    pub scroll: (f64, f64),
+}
+
+fn session_path(name: &str) -> Result<PathBuf, Error> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(Error::BadName {
+            name: name.to_owned(),
+        });
+    }
+    state::path(&format!("{}/{}.json", SESSIONS_DIR, name)).context(State)
+}
+
+pub fn load(name: &str) -> Result<Session, Error> {
+    let path = session_path(name)?;
+    state::read(&path)
+        .context(State)?
+        .ok_or_else(|| Error::NotFound {
+            name: name.to_owned(),
+        })
+}
+
+/// Writes `session` as `name`, returning the path of the session file.
+pub fn save(name: &str, session: &Session) -> Result<PathBuf, Error> {
+    let path = session_path(name)?;
+    state::write(&path, session).context(State)?;
+    Ok(path)
+}
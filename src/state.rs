@@ -0,0 +1,64 @@
+//! JSON files kept under `$XDG_STATE_HOME/iv`.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use snafu::{OptionExt, ResultExt, Snafu};
+
+use crate::xdg;
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("Can't find a state directory, set $XDG_STATE_HOME or $HOME"))]
+    NoStateDir,
+
+    #[snafu(display("Can't read {}: {}", path.display(), source))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Can't parse {}: {}", path.display(), source))]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[snafu(display("Can't write {}: {}", path.display(), source))]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The file `name` relative to the state directory of iv.
+pub fn path(name: &str) -> Result<PathBuf, Error> {
+    xdg::state_home()
+        .map(|dir| dir.join("iv").join(name))
+        .context(NoStateDir)
+}
+
+/// Parses the JSON file at `path`, `None` if it doesn't exist.
+pub fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).context(Read { path })?;
+    serde_json::from_str(&content)
+        .context(Parse { path })
+        .map(Some)
+}
+
+/// Writes `value` as JSON to `path`, through a temporary file so a crash never
+/// leaves a truncated file behind.
+pub fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(Write { path })?;
+    }
+    let content = serde_json::to_string(value).expect("State can always be serialized");
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content).context(Write { path: &tmp })?;
+    fs::rename(&tmp, path).context(Write { path })
+}
@@ -0,0 +1,705 @@
+//! Format strings with conditionals and expressions on top of `formatter`.
+//!
+//! A template is literal text mixed with:
+//! - `{expr}` or `{expr:spec}` where `spec` is passed on to `formatter`,
+//!   e.g. `{filesize:B.2}` or `{width*height/1e6:.1}`
+//! - `{expr|trunc(20)}` to cut text after 20 chars and `{expr|ellipsize(20)}`
+//!   to replace the middle with `…`, numbers are shortened after formatting
+//! - `{?expr}...{/}` to only show the enclosed part if `expr` is truthy
+//! - `{{` and `}}` for literal braces
+//!
+//! Expressions support numbers, 'strings', keys, `+ - * /`, comparisons
+//! (`== != < <= > >=`), `!` and parentheses. Keys without a value
+//! evaluate to nothing which hides placeholders and fails conditions.
+use std::collections::BTreeMap;
+
+use formatter::{FormatMap, FormatString};
+use serde::Serialize;
+use snafu::{ResultExt, Snafu};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Text(s)
+    }
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(n) => *n != 0.,
+            Value::Text(s) => !s.is_empty(),
+        }
+    }
+}
+
+/// Sorted by key so `iv info --json` prints the same keys in the same order every time.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Values(BTreeMap<&'static str, Value>);
+
+impl Values {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert<V: Into<Value>>(&mut self, key: &'static str, value: V) {
+        self.0.insert(key, value.into());
+    }
+
+    pub fn remove(&mut self, key: &'static str) {
+        self.0.remove(key);
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+}
+
+#[derive(Snafu, Debug)]
+pub enum Error {
+    #[snafu(display("Unknown key `{}` at column {}", key, column))]
+    UnknownKey { key: String, column: usize },
+
+    #[snafu(display("Unknown function `{}` at column {}", name, column))]
+    UnknownFunction { name: String, column: usize },
+
+    #[snafu(display("{} at column {}", msg, column))]
+    Syntax { msg: String, column: usize },
+
+    #[snafu(display("Bad format spec at column {}: {}", column, source))]
+    Spec {
+        column: usize,
+        source: formatter::Error,
+    },
+
+    #[snafu(display("Can't format: {}", source))]
+    Render { source: formatter::Error },
+}
+
+impl Error {
+    /// 1-based column in the template the error was found at.
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            Error::UnknownKey { column, .. }
+            | Error::UnknownFunction { column, .. }
+            | Error::Syntax { column, .. }
+            | Error::Spec { column, .. } => Some(*column),
+            Error::Render { .. } => None,
+        }
+    }
+}
+
+fn syntax<T>(msg: impl Into<String>, column: usize) -> Result<T, Error> {
+    Err(Error::Syntax {
+        msg: msg.into(),
+        column,
+    })
+}
+
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Placeholder {
+        expr: Expr,
+        filters: Vec<Filter>,
+        format: FormatString,
+    },
+    Conditional {
+        cond: Expr,
+        body: Vec<Part>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Filter {
+    Trunc(usize),
+    Ellipsize(usize),
+}
+
+enum Expr {
+    Value(Value),
+    Key(String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Template {
+    pub fn parse(template: &str, allowed_keys: &[&str]) -> Result<Self, Error> {
+        let chars: Vec<char> = template.chars().collect();
+        let mut pos = 0;
+        let parts = parse_parts(&chars, &mut pos, allowed_keys, None)?;
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, values: &Values) -> Result<String, Error> {
+        let mut ret = String::new();
+        render_parts(&self.parts, values, &mut ret)?;
+        Ok(ret)
+    }
+}
+
+/// Parses literal text and placeholders until the end of input or, when
+/// `open` is the column of an enclosing `{?...}`, until the matching `{/}`.
+fn parse_parts(
+    chars: &[char],
+    pos: &mut usize,
+    allowed_keys: &[&str],
+    open: Option<usize>,
+) -> Result<Vec<Part>, Error> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    while *pos < chars.len() {
+        match (chars[*pos], chars.get(*pos + 1)) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                literal.push(chars[*pos]);
+                *pos += 2;
+            }
+            ('}', _) => return syntax("Unmatched `}`", *pos + 1),
+            ('{', _) => {
+                let start = *pos;
+                let end = match find_unquoted(&chars[start..], '}') {
+                    Some(len) => start + len,
+                    None => return syntax("Unclosed `{`", start + 1),
+                };
+                let inner = &chars[start + 1..end];
+                *pos = end + 1;
+
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::replace(&mut literal, String::new())));
+                }
+
+                match inner.first() {
+                    Some('/') if inner.len() == 1 => {
+                        return match open {
+                            Some(_) => Ok(parts),
+                            None => syntax("`{/}` without matching `{?...}`", start + 1),
+                        };
+                    }
+                    Some('?') => {
+                        let cond = parse_expr(&inner[1..], start + 3, allowed_keys)?;
+                        let body = parse_parts(chars, pos, allowed_keys, Some(start + 1))?;
+                        parts.push(Part::Conditional { cond, body });
+                    }
+                    _ => parts.push(parse_placeholder(inner, start + 2, allowed_keys)?),
+                }
+            }
+            (c, _) => {
+                literal.push(c);
+                *pos += 1;
+            }
+        }
+    }
+
+    if let Some(column) = open {
+        return syntax("Missing `{/}` for conditional", column);
+    }
+
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+
+    Ok(parts)
+}
+
+/// Finds the first `needle` that's not inside a string literal.
+fn find_unquoted(chars: &[char], needle: char) -> Option<usize> {
+    let mut in_str = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '\'' {
+            in_str = !in_str;
+        } else if c == needle && !in_str {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn parse_placeholder(inner: &[char], column: usize, allowed_keys: &[&str]) -> Result<Part, Error> {
+    let (body, spec) = match find_unquoted(inner, ':') {
+        Some(i) => (&inner[..i], Some(&inner[i..])),
+        None => (inner, None),
+    };
+
+    let (expr_chars, mut filter_chars) = match find_unquoted(body, '|') {
+        Some(i) => (&body[..i], Some((&body[i + 1..], column + i + 1))),
+        None => (body, None),
+    };
+    let expr = parse_expr(expr_chars, column, allowed_keys)?;
+
+    let mut filters = Vec::new();
+    while let Some((chars, column)) = filter_chars {
+        let (filter, rest) = match find_unquoted(chars, '|') {
+            Some(i) => (&chars[..i], Some((&chars[i + 1..], column + i + 1))),
+            None => (chars, None),
+        };
+        filters.push(parse_filter(filter, column)?);
+        filter_chars = rest;
+    }
+
+    let spec: String = spec.map(|spec| spec.iter().collect()).unwrap_or_default();
+    let format = FormatString::parse_with_allowed_keys(&format!("{{v{}}}", spec), &["v"])
+        .context(Spec {
+            column: column + body.len(),
+        })?;
+
+    Ok(Part::Placeholder {
+        expr,
+        filters,
+        format,
+    })
+}
+
+fn parse_filter(chars: &[char], column: usize) -> Result<Filter, Error> {
+    let s: String = chars.iter().collect();
+    let s = s.trim();
+    let (name, arg) = match (s.find('('), s.ends_with(')')) {
+        (Some(open), true) => (&s[..open], &s[open + 1..s.len() - 1]),
+        _ => return syntax("Expected a function call like `trunc(20)`", column),
+    };
+    let len = match arg.trim().parse::<usize>() {
+        Ok(len) => len,
+        Err(_) => return syntax("Expected a length", column),
+    };
+    match name.trim() {
+        "trunc" => Ok(Filter::Trunc(len)),
+        "ellipsize" => Ok(Filter::Ellipsize(len)),
+        other => Err(Error::UnknownFunction {
+            name: other.to_owned(),
+            column,
+        }),
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPS: &[&str] = &[
+    "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "!", "(", ")",
+];
+
+fn tokenize(chars: &[char], column: usize) -> Result<Vec<(Token, usize)>, Error> {
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric()
+                    || chars[i] == '.'
+                    || ((chars[i] == '-' || chars[i] == '+')
+                        && (chars[i - 1] == 'e' || chars[i - 1] == 'E')))
+            {
+                i += 1;
+            }
+            let num: String = chars[start..i].iter().collect();
+            match num.parse::<f64>() {
+                Ok(n) => ret.push((Token::Num(n), column + start)),
+                Err(_) => return syntax(format!("Invalid number `{}`", num), column + start),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            ret.push((
+                Token::Ident(chars[start..i].iter().collect()),
+                column + start,
+            ));
+        } else if c == '\'' {
+            match chars[i + 1..].iter().position(|&c| c == '\'') {
+                Some(len) => {
+                    ret.push((
+                        Token::Str(chars[i + 1..i + 1 + len].iter().collect()),
+                        column + start,
+                    ));
+                    i += len + 2;
+                }
+                None => return syntax("Unclosed string", column + start),
+            }
+        } else {
+            let op = OPS.iter().find(|op| {
+                op.chars()
+                    .enumerate()
+                    .all(|(j, c)| chars.get(i + j) == Some(&c))
+            });
+            match op {
+                Some(op) => {
+                    ret.push((Token::Op(op), column + start));
+                    i += op.len();
+                }
+                None => return syntax(format!("Unexpected `{}`", c), column + start),
+            }
+        }
+    }
+    Ok(ret)
+}
+
+struct ExprParser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    end: usize,
+    allowed_keys: &'a [&'a str],
+}
+
+fn parse_expr(chars: &[char], column: usize, allowed_keys: &[&str]) -> Result<Expr, Error> {
+    let mut parser = ExprParser {
+        tokens: tokenize(chars, column)?,
+        pos: 0,
+        end: column + chars.len(),
+        allowed_keys,
+    };
+    let expr = parser.comparison()?;
+    match parser.tokens.get(parser.pos) {
+        Some((_, column)) => syntax("Unexpected trailing input", *column),
+        None => Ok(expr),
+    }
+}
+
+impl<'a> ExprParser<'a> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some((Token::Op(op), _)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|(_, column)| *column)
+            .unwrap_or(self.end)
+    }
+
+    fn binary<F>(&mut self, ops: &[(&str, BinOp)], next: F) -> Result<Expr, Error>
+    where
+        F: Fn(&mut Self) -> Result<Expr, Error>,
+    {
+        let mut lhs = next(self)?;
+        while let Some(op) = self
+            .peek_op()
+            .and_then(|peeked| ops.iter().find(|(op, _)| *op == peeked))
+            .map(|(_, op)| *op)
+        {
+            self.pos += 1;
+            let rhs = next(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, Error> {
+        self.binary(
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expr, Error> {
+        self.binary(&[("+", BinOp::Add), ("-", BinOp::Sub)], Self::term)
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        self.binary(&[("*", BinOp::Mul), ("/", BinOp::Div)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        match self.peek_op() {
+            Some("!") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let column = self.column();
+        let token = match self.tokens.get(self.pos) {
+            Some((token, _)) => token.clone(),
+            None => return syntax("Expected an expression", column),
+        };
+        self.pos += 1;
+        match token {
+            Token::Num(n) => Ok(Expr::Value(Value::Number(n))),
+            Token::Str(s) => Ok(Expr::Value(Value::Text(s))),
+            Token::Ident(key) => {
+                if self.allowed_keys.contains(&key.as_str()) {
+                    Ok(Expr::Key(key))
+                } else {
+                    Err(Error::UnknownKey { key, column })
+                }
+            }
+            Token::Op("(") => {
+                let expr = self.comparison()?;
+                if self.peek_op() == Some(")") {
+                    self.pos += 1;
+                    Ok(expr)
+                } else {
+                    syntax("Expected `)`", self.column())
+                }
+            }
+            Token::Op(op) => syntax(format!("Unexpected `{}`", op), column),
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, values: &Values) -> Option<Value> {
+        match self {
+            Expr::Value(value) => Some(value.clone()),
+            Expr::Key(key) => values.get(key).cloned(),
+            Expr::Not(expr) => {
+                let truthy = expr.eval(values).map_or(false, |value| value.is_truthy());
+                Some(Value::Number(if truthy { 0. } else { 1. }))
+            }
+            Expr::Neg(expr) => match expr.eval(values)? {
+                Value::Number(n) => Some(Value::Number(-n)),
+                Value::Text(_) => None,
+            },
+            Expr::Binary(op, lhs, rhs) => eval_binary(*op, lhs.eval(values)?, rhs.eval(values)?),
+        }
+    }
+}
+
+fn eval_binary(op: BinOp, lhs: Value, rhs: Value) -> Option<Value> {
+    use std::cmp::Ordering;
+
+    let ordering = match (&lhs, &rhs) {
+        (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    let cmp = |f: fn(Ordering) -> bool| {
+        ordering.map(|ordering| Value::Number(if f(ordering) { 1. } else { 0. }))
+    };
+
+    match (op, lhs, rhs) {
+        (BinOp::Add, Value::Text(a), Value::Text(b)) => Some(Value::Text(a + &b)),
+        (BinOp::Add, Value::Number(a), Value::Number(b)) => Some(Value::Number(a + b)),
+        (BinOp::Sub, Value::Number(a), Value::Number(b)) => Some(Value::Number(a - b)),
+        (BinOp::Mul, Value::Number(a), Value::Number(b)) => Some(Value::Number(a * b)),
+        (BinOp::Div, Value::Number(a), Value::Number(b)) => Some(Value::Number(a / b)),
+        (BinOp::Eq, ..) => cmp(|o| o == Ordering::Equal),
+        (BinOp::Ne, ..) => cmp(|o| o != Ordering::Equal),
+        (BinOp::Lt, ..) => cmp(|o| o == Ordering::Less),
+        (BinOp::Le, ..) => cmp(|o| o != Ordering::Greater),
+        (BinOp::Gt, ..) => cmp(|o| o == Ordering::Greater),
+        (BinOp::Ge, ..) => cmp(|o| o != Ordering::Less),
+        _ => None,
+    }
+}
+
+impl Filter {
+    fn apply(self, s: String) -> String {
+        let len = s.chars().count();
+        match self {
+            Filter::Trunc(max) | Filter::Ellipsize(max) if len <= max => s,
+            Filter::Trunc(max) => s.chars().take(max).collect(),
+            // not even room for the ellipsis
+            Filter::Ellipsize(0) => String::new(),
+            Filter::Ellipsize(max) => {
+                let keep = max.saturating_sub(1);
+                let tail = keep / 2;
+                let head = keep - tail;
+                let mut ret: String = s.chars().take(head).collect();
+                ret.push('…');
+                ret.extend(s.chars().skip(len - tail));
+                ret
+            }
+        }
+    }
+}
+
+fn render_parts(parts: &[Part], values: &Values, out: &mut String) -> Result<(), Error> {
+    for part in parts {
+        match part {
+            Part::Literal(s) => out.push_str(s),
+            Part::Conditional { cond, body } => {
+                if cond.eval(values).map_or(false, |value| value.is_truthy()) {
+                    render_parts(body, values, out)?;
+                }
+            }
+            Part::Placeholder {
+                expr,
+                filters,
+                format,
+            } => {
+                let apply = |s: String| filters.iter().fold(s, |s, filter| filter.apply(s));
+                let mut map = FormatMap::new();
+                let is_number = match expr.eval(values) {
+                    Some(Value::Number(n)) => {
+                        map.insert("v", n);
+                        true
+                    }
+                    Some(Value::Text(s)) => {
+                        map.insert("v", apply(s));
+                        false
+                    }
+                    None => continue,
+                };
+                let formatted = format.fmt(&map).context(Render)?.to_string();
+                if is_number {
+                    out.push_str(&apply(formatted));
+                } else {
+                    out.push_str(&formatted);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: &[&str] = &["filename", "width", "height", "archive", "page"];
+
+    fn values() -> Values {
+        let mut values = Values::new();
+        values.insert("filename", "holiday_photo.jpg".to_string());
+        values.insert("width", 1920.);
+        values.insert("height", 1080.);
+        values
+    }
+
+    fn render(template: &str) -> String {
+        Template::parse(template, KEYS)
+            .unwrap()
+            .render(&values())
+            .unwrap()
+    }
+
+    fn error_column(template: &str) -> Option<usize> {
+        Template::parse(template, KEYS)
+            .err()
+            .and_then(|e| e.column())
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(render("iv - {filename}"), "iv - holiday_photo.jpg");
+        assert_eq!(render("{width:.0}x{height:.0}"), "1920x1080");
+        assert_eq!(render("{width*height/1e6:.1}MP"), "2.1MP");
+        assert_eq!(render("{'a' + 'b'}"), "ab");
+    }
+
+    #[test]
+    fn missing_values_are_left_out() {
+        assert_eq!(render("[{archive}]"), "[]");
+        assert_eq!(render("{page + 1:.0}"), "");
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(
+            render("{?archive}{archive}:{/}{filename}"),
+            "holiday_photo.jpg"
+        );
+        assert_eq!(render("{?width > height}wide{/}"), "wide");
+        assert_eq!(render("{?!archive}none{/}"), "none");
+        assert_eq!(render("{?width}{?height < 100}small{/}big{/}"), "big");
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(render("{{filename}}"), "{filename}");
+        assert_eq!(render("{'}'}"), "}");
+        assert_eq!(render("{?filename != '}'}x{/}"), "x");
+        assert_eq!(render("{'a|b:c'}"), "a|b:c");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(render("{filename|trunc(7)}"), "holiday");
+        assert_eq!(render("{filename|trunc(0)}"), "");
+        assert_eq!(render("{filename|trunc(100)}"), "holiday_photo.jpg");
+        assert_eq!(render("{filename|ellipsize(9)}"), "holi….jpg");
+        assert_eq!(render("{filename|ellipsize(100)}"), "holiday_photo.jpg");
+        assert_eq!(render("{filename|ellipsize(1)}"), "…");
+        assert_eq!(render("{filename|ellipsize(0)}"), "");
+        assert_eq!(render("{filename|trunc(9)|ellipsize(5)}"), "ho…_p");
+    }
+
+    #[test]
+    fn filters_apply_to_formatted_numbers() {
+        assert_eq!(render("{width|trunc(2):.0}"), "19");
+        assert_eq!(render("{width / 7|ellipsize(5):.3}"), "27…86");
+    }
+
+    #[test]
+    fn errors() {
+        match Template::parse("{nope}", KEYS) {
+            Err(Error::UnknownKey { key, .. }) => assert_eq!(key, "nope"),
+            _ => panic!("`nope` isn't an allowed key"),
+        }
+        match Template::parse("{filename|shout(2)}", KEYS) {
+            Err(Error::UnknownFunction { name, .. }) => assert_eq!(name, "shout"),
+            _ => panic!("`shout` isn't a filter"),
+        }
+        assert_eq!(error_column("ab {nope}"), Some(5));
+        assert_eq!(error_column("ab }"), Some(4));
+        assert_eq!(error_column("ab {filename"), Some(4));
+        assert_eq!(error_column("{?width}x"), Some(1));
+        assert_eq!(error_column("x{/}"), Some(2));
+        assert_eq!(error_column("{'}"), Some(1));
+        assert_eq!(error_column("{filename|trunc(x)}"), Some(11));
+        assert_eq!(error_column("{width +}"), Some(9));
+    }
+
+    #[test]
+    fn values_serialize_sorted_by_key() {
+        assert_eq!(
+            serde_json::to_string(&values()).unwrap(),
+            r#"{"filename":"holiday_photo.jpg","height":1080.0,"width":1920.0}"#
+        );
+    }
+}
@@ -1,14 +1,27 @@
-use std::convert::TryFrom;
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Reverse,
+    convert::TryFrom,
+    ops::Range,
+    rc::Rc,
+    time::Duration,
+};
 
 use cascade::cascade;
 use euclid::{vec2, Vector2D};
 use gtk::prelude::*;
 
-use crate::{events::UserEvent, math::Pixels};
+use crate::{config::StatusBarPosition, events::UserEvent, fuzzy, math::Pixels};
+
+/// Most file names the picker lists at once.
+const MAX_PICKER_ROWS: usize = 100;
 
 pub struct Main {
     vbox: gtk::Box,
-    bottom_bar: BottomBar,
+    status_bar: StatusBar,
+    metadata: MetadataPanel,
+    help: Help,
+    picker: Picker,
     pub image: ScrollableImage,
 }
 
@@ -18,21 +31,116 @@ impl AsRef<gtk::Box> for Main {
     }
 }
 
-pub struct BottomBar {
+pub struct StatusBar {
     hbox: gtk::Box,
-    info: gtk::Label,
-    _err: gtk::Label,
+    left: gtk::Label,
+    center: gtk::Label,
+    right: gtk::Label,
+    /// Errors and other short lived messages.
+    message: gtk::Label,
+    spinner: gtk::Spinner,
+    message_timeout: Rc<RefCell<Option<glib::SourceId>>>,
+    prompt: Prompt,
+}
+
+/// What the text entered in the prompt is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    Goto,
+    SessionName,
+}
+
+impl PromptKind {
+    fn placeholder(self) -> &'static str {
+        match self {
+            PromptKind::Goto => "index, +/-offset or file name",
+            PromptKind::SessionName => "session name, empty for the current time",
+        }
+    }
+}
+
+/// Text entry in the status bar that shows the bar while it's open.
+#[derive(Clone)]
+struct Prompt {
+    entry: gtk::Entry,
+    bar: gtk::Box,
+    bar_was_hidden: Rc<Cell<bool>>,
+    kind: Rc<Cell<PromptKind>>,
 }
 
-impl AsRef<gtk::Box> for BottomBar {
+impl Prompt {
+    fn show(&self, kind: PromptKind) {
+        self.kind.set(kind);
+        self.bar_was_hidden.set(!self.bar.get_visible());
+        self.bar.show();
+        self.entry.set_placeholder_text(Some(kind.placeholder()));
+        self.entry.set_text("");
+        self.entry.show();
+        self.entry.grab_focus();
+    }
+
+    fn hide(&self) {
+        self.entry.hide();
+        if self.bar_was_hidden.get() {
+            self.bar.hide();
+        }
+    }
+}
+
+impl AsRef<gtk::Box> for StatusBar {
     fn as_ref(&self) -> &gtk::Box {
         &self.hbox
     }
 }
 
+pub struct MetadataPanel {
+    scroll: gtk::ScrolledWindow,
+    grid: gtk::Grid,
+}
+
+impl AsRef<gtk::ScrolledWindow> for MetadataPanel {
+    fn as_ref(&self) -> &gtk::ScrolledWindow {
+        &self.scroll
+    }
+}
+
+/// Overlay for choosing an image by fuzzy searching the file names.
+#[derive(Clone)]
+pub struct Picker {
+    vbox: gtk::Box,
+    entry: gtk::Entry,
+    scroll: gtk::ScrolledWindow,
+    list: gtk::ListBox,
+    names: Rc<RefCell<Vec<String>>>,
+    /// Index into `names` of every row in `list`.
+    rows: Rc<RefCell<Vec<usize>>>,
+}
+
+impl AsRef<gtk::Box> for Picker {
+    fn as_ref(&self) -> &gtk::Box {
+        &self.vbox
+    }
+}
+
+pub struct Help {
+    scroll: gtk::ScrolledWindow,
+    grid: gtk::Grid,
+}
+
+impl AsRef<gtk::ScrolledWindow> for Help {
+    fn as_ref(&self) -> &gtk::ScrolledWindow {
+        &self.scroll
+    }
+}
+
 pub struct ScrollableImage {
     scroll: gtk::ScrolledWindow,
     pub image: gtk::Image,
+    broken: gtk::Label,
+    /// Replaces `image` with one image per page in strip layout.
+    strip: gtk::Box,
+    /// The children of `strip` so pages can be looked up without walking the box.
+    strip_pages: RefCell<Vec<gtk::Image>>,
 }
 
 impl AsRef<gtk::ScrolledWindow> for ScrollableImage {
@@ -41,40 +149,446 @@ impl AsRef<gtk::ScrolledWindow> for ScrollableImage {
     }
 }
 
-impl BottomBar {
+#[derive(Clone, Copy, Debug)]
+pub enum Segment {
+    Left,
+    Center,
+    Right,
+}
+
+impl StatusBar {
     pub fn new() -> Self {
-        let info = cascade! {
+        let left = cascade! {
             gtk::Label::new(None);
+            ..get_style_context().add_class("status-left");
         };
 
-        let _err = cascade! {
+        let center = cascade! {
             gtk::Label::new(None);
+            ..get_style_context().add_class("status-center");
+        };
+
+        let right = cascade! {
+            gtk::Label::new(None);
+            ..get_style_context().add_class("status-right");
+        };
+
+        let message = cascade! {
+            gtk::Label::new(None);
+            ..get_style_context().add_class("message");
+        };
+
+        let spinner = cascade! {
+            gtk::Spinner::new();
+            ..set_no_show_all(true);
+        };
+
+        let entry = cascade! {
+            gtk::Entry::new();
+            ..set_no_show_all(true);
         };
 
         let hbox = cascade! {
             gtk::Box::new(gtk::Orientation::Horizontal, 10);
-            ..pack_start(&_err, false, false, 0);
-            ..pack_end(&info, true, true, 0);
-            ..set_valign(gtk::Align::End);
-            ..set_halign(gtk::Align::End);
+            ..pack_start(&spinner, false, false, 0);
+            ..pack_start(&entry, true, true, 0);
+            ..pack_start(&message, false, false, 0);
+            ..pack_start(&left, false, false, 0);
+            ..set_center_widget(Some(&center));
+            ..pack_end(&right, false, false, 0);
+            ..get_style_context().add_class("status-bar");
         };
 
-        Self { hbox, info, _err }
+        let prompt = Prompt {
+            entry,
+            bar: hbox.clone(),
+            bar_was_hidden: Rc::new(Cell::new(false)),
+            kind: Rc::new(Cell::new(PromptKind::Goto)),
+        };
+        let escape = prompt.clone();
+        prompt.entry.connect_key_press_event(move |_, key_evt| {
+            if key_evt.get_keyval() == gdk::enums::key::Escape {
+                escape.hide();
+                Inhibit(true)
+            } else {
+                Inhibit(false)
+            }
+        });
+
+        Self {
+            hbox,
+            left,
+            center,
+            right,
+            message,
+            spinner,
+            message_timeout: Rc::new(RefCell::new(None)),
+            prompt,
+        }
+    }
+
+    fn set_text(&self, segment: Segment, text: &str) {
+        let label = match segment {
+            Segment::Left => &self.left,
+            Segment::Center => &self.center,
+            Segment::Right => &self.right,
+        };
+        label.set_text(text);
+    }
+
+    fn set_loading(&self, loading: bool) {
+        if loading {
+            self.spinner.show();
+            self.spinner.start();
+        } else {
+            self.spinner.stop();
+            self.spinner.hide();
+        }
+    }
+
+    fn cancel_message_timeout(&self) {
+        if let Some(id) = self.message_timeout.borrow_mut().take() {
+            glib::source_remove(id);
+        }
+    }
+
+    /// Shows `text` in the message label and clears it again after `duration`.
+    fn show_message(&self, text: &str, duration: Duration, is_error: bool) {
+        self.cancel_message_timeout();
+        let style = self.message.get_style_context();
+        if is_error {
+            style.add_class("error");
+        } else {
+            style.remove_class("error");
+        }
+        self.message.set_text(text);
+
+        let message = self.message.clone();
+        let message_timeout = self.message_timeout.clone();
+        let id = glib::timeout_add_local(duration.as_millis() as u32, move || {
+            message.set_text("");
+            message_timeout.borrow_mut().take();
+            glib::Continue(false)
+        });
+        *self.message_timeout.borrow_mut() = Some(id);
+    }
+}
+
+impl MetadataPanel {
+    pub fn new() -> Self {
+        let grid = cascade! {
+            gtk::Grid::new();
+            ..set_column_spacing(10);
+            ..set_row_spacing(2);
+            ..set_margin_start(5);
+            ..set_margin_end(5);
+        };
+
+        let viewport = cascade! {
+            gtk::Viewport::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+            ..add(&grid);
+        };
+
+        // not shown by show_all so the panel starts hidden
+        let scroll = cascade! {
+            gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+            ..set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+            ..set_no_show_all(true);
+            ..add(&viewport);
+        };
+        viewport.show_all();
+
+        Self { scroll, grid }
+    }
+
+    fn set_tags(&self, tags: &[(String, String)]) {
+        for child in self.grid.get_children() {
+            self.grid.remove(&child);
+        }
+
+        if tags.is_empty() {
+            self.grid
+                .attach(&gtk::Label::new(Some("No metadata")), 0, 0, 2, 1);
+        }
+
+        for (row, (name, value)) in tags.iter().enumerate() {
+            let name = cascade! {
+                gtk::Label::new(Some(name.as_str()));
+                ..set_xalign(0.);
+                ..get_style_context().add_class("dim-label");
+            };
+            let value = cascade! {
+                gtk::Label::new(Some(value.as_str()));
+                ..set_xalign(0.);
+                ..set_selectable(true);
+                ..set_line_wrap(true);
+            };
+            self.grid.attach(&name, 0, row as i32, 1, 1);
+            self.grid.attach(&value, 1, row as i32, 1, 1);
+        }
+
+        self.grid.show_all();
+    }
+
+    fn toggle(&self) {
+        if self.scroll.get_visible() {
+            self.scroll.hide();
+        } else {
+            self.scroll.show();
+        }
+    }
+}
+
+impl Help {
+    pub fn new() -> Self {
+        let grid = cascade! {
+            gtk::Grid::new();
+            ..set_column_spacing(20);
+            ..set_row_spacing(2);
+            ..set_margin_top(10);
+            ..set_margin_bottom(10);
+            ..set_margin_start(10);
+            ..set_margin_end(10);
+        };
+
+        let viewport = cascade! {
+            gtk::Viewport::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+            ..add(&grid);
+        };
+
+        let scroll = cascade! {
+            gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+            ..set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+            ..set_propagate_natural_height(true);
+            ..set_halign(gtk::Align::Center);
+            ..set_valign(gtk::Align::Center);
+            ..set_no_show_all(true);
+            ..get_style_context().add_class("osd");
+            ..add(&viewport);
+        };
+        viewport.show_all();
+
+        Self { scroll, grid }
+    }
+
+    fn set_bindings(&self, bindings: &[(UserEvent, Vec<String>)]) {
+        for child in self.grid.get_children() {
+            self.grid.remove(&child);
+        }
+
+        for (row, (action, keys)) in bindings.iter().enumerate() {
+            let columns = [
+                keys.join(", "),
+                action.name().to_owned(),
+                action.description().to_owned(),
+            ];
+            for (column, text) in columns.iter().enumerate() {
+                let label = cascade! {
+                    gtk::Label::new(Some(text.as_str()));
+                    ..set_xalign(0.);
+                };
+                if column == 0 {
+                    label.get_style_context().add_class("dim-label");
+                }
+                self.grid.attach(&label, column as i32, row as i32, 1, 1);
+            }
+        }
+
+        self.grid.show_all();
+    }
+
+    fn toggle(&self) {
+        if self.scroll.get_visible() {
+            self.scroll.hide();
+        } else {
+            self.scroll.show();
+        }
     }
 }
 
+impl Picker {
+    pub fn new() -> Self {
+        let entry = gtk::Entry::new();
+
+        let list = cascade! {
+            gtk::ListBox::new();
+            ..set_selection_mode(gtk::SelectionMode::Browse);
+        };
+
+        let scroll = cascade! {
+            gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
+            ..set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+            ..set_vexpand(true);
+            ..add(&list);
+        };
+
+        let vbox = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 5);
+            ..set_size_request(500, 400);
+            ..set_halign(gtk::Align::Center);
+            ..set_valign(gtk::Align::Center);
+            ..set_no_show_all(true);
+            ..get_style_context().add_class("osd");
+            ..pack_start(&entry, false, false, 0);
+            ..pack_start(&scroll, true, true, 0);
+        };
+        entry.show();
+        scroll.show_all();
+
+        let picker = Self {
+            vbox,
+            entry,
+            scroll,
+            list,
+            names: Rc::new(RefCell::new(Vec::new())),
+            rows: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        let changed = picker.clone();
+        picker.entry.connect_changed(move |_| changed.refilter());
+
+        let keys = picker.clone();
+        picker.entry.connect_key_press_event(move |_, key_evt| {
+            use gdk::enums::key;
+            match key_evt.get_keyval() {
+                key::Escape => keys.hide(),
+                key::Down => keys.move_selection(1),
+                key::Up => keys.move_selection(-1),
+                _ => return Inhibit(false),
+            }
+            Inhibit(true)
+        });
+
+        picker
+    }
+
+    fn show(&self, names: Vec<String>) {
+        *self.names.borrow_mut() = names;
+        self.vbox.show();
+        // an unchanged entry doesn't emit changed
+        if self.entry.get_text().map_or(true, |text| text.is_empty()) {
+            self.refilter();
+        } else {
+            self.entry.set_text("");
+        }
+        self.entry.grab_focus();
+    }
+
+    fn hide(&self) {
+        self.vbox.hide();
+        self.names.borrow_mut().clear();
+    }
+
+    /// Lists the best matches for the text in the entry.
+    fn refilter(&self) {
+        let pattern = self.entry.get_text().map(|text| text.to_string());
+        let pattern = pattern.unwrap_or_default();
+        let names = self.names.borrow();
+        let mut matches: Vec<_> = names
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| {
+                fuzzy::score(&pattern, name).map(|(score, positions)| (score, i, positions))
+            })
+            .collect();
+        matches.sort_by_key(|&(score, i, _)| (Reverse(score), i));
+        matches.truncate(MAX_PICKER_ROWS);
+
+        for child in self.list.get_children() {
+            self.list.remove(&child);
+        }
+        let mut rows = self.rows.borrow_mut();
+        rows.clear();
+        for (_, i, positions) in matches {
+            let label = cascade! {
+                gtk::Label::new(None);
+                ..set_xalign(0.);
+                ..set_markup(&highlight(&names[i], &positions));
+            };
+            self.list.add(&label);
+            rows.push(i);
+        }
+        self.list.show_all();
+
+        if let Some(row) = self.list.get_row_at_index(0) {
+            self.list.select_row(Some(&row));
+        }
+    }
+
+    fn move_selection(&self, by: i32) {
+        let current = self
+            .list
+            .get_selected_row()
+            .map_or(0, |row| row.get_index());
+        if let Some(row) = self.list.get_row_at_index(current + by) {
+            self.list.select_row(Some(&row));
+            if let Some(adjust) = self.scroll.get_vadjustment() {
+                let alloc = row.get_allocation();
+                adjust.clamp_page(f64::from(alloc.y), f64::from(alloc.y + alloc.height));
+            }
+        }
+    }
+
+    /// Index of the selected file name in the names passed to `show`.
+    fn selected(&self) -> Option<usize> {
+        let row = self.list.get_selected_row()?;
+        self.rows.borrow().get(row.get_index() as usize).copied()
+    }
+}
+
+/// Pango markup of `text` with the chars at `positions` in bold.
+fn highlight(text: &str, positions: &[usize]) -> String {
+    let mut markup = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        let escaped = glib::markup_escape_text(c.encode_utf8(&mut [0; 4]));
+        if positions.binary_search(&i).is_ok() {
+            markup.push_str("<b>");
+            markup.push_str(&escaped);
+            markup.push_str("</b>");
+        } else {
+            markup.push_str(&escaped);
+        }
+    }
+    markup
+}
+
 impl ScrollableImage {
     pub fn new() -> Self {
         let image = cascade! {
             gtk::Image::new();
         };
 
+        let broken = cascade! {
+            gtk::Label::new(None);
+            ..set_no_show_all(true);
+            ..set_line_wrap(true);
+        };
+
+        let strip = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 0);
+            ..set_no_show_all(true);
+        };
+
+        let vbox = cascade! {
+            gtk::Box::new(gtk::Orientation::Vertical, 10);
+            ..set_valign(gtk::Align::Center);
+            ..pack_start(&image, true, true, 0);
+            ..pack_start(&broken, false, false, 0);
+            ..pack_start(&strip, false, false, 0);
+        };
+
         let scroll = cascade! {
             gtk::ScrolledWindow::new::<gtk::Adjustment, gtk::Adjustment>(None, None);
-            ..add(&image);
+            ..add(&vbox);
         };
 
-        Self { scroll, image }
+        Self {
+            scroll,
+            image,
+            broken,
+            strip,
+            strip_pages: RefCell::new(Vec::new()),
+        }
     }
 }
 
@@ -117,62 +631,364 @@ impl TryFrom<UserEvent> for Scroll {
     }
 }
 
+fn scroll_window(window: &gtk::ScrolledWindow, scroll: Scroll) {
+    use Scroll::*;
+    match scroll {
+        H(scroll) => {
+            if let Some(adjust) = window.get_hadjustment() {
+                use ScrollH::*;
+                match scroll {
+                    Left => adjust.set_value(adjust.get_value() - adjust.get_step_increment()),
+                    Right => adjust.set_value(adjust.get_value() + adjust.get_step_increment()),
+                    Start => adjust.set_value(adjust.get_lower()),
+                    End => adjust.set_value(adjust.get_upper()),
+                }
+            }
+        }
+        V(scroll) => {
+            if let Some(adjust) = window.get_vadjustment() {
+                use ScrollV::*;
+                match scroll {
+                    Up => adjust.set_value(adjust.get_value() - adjust.get_step_increment()),
+                    Down => adjust.set_value(adjust.get_value() + adjust.get_step_increment()),
+                    Start => adjust.set_value(adjust.get_lower()),
+                    End => adjust.set_value(adjust.get_upper()),
+                }
+            }
+        }
+    }
+}
+
 impl Main {
-    pub fn new() -> Self {
-        let bottom_bar = BottomBar::new();
+    pub fn new(status_position: StatusBarPosition) -> Self {
+        let status_bar = StatusBar::new();
 
         let image = ScrollableImage::new();
 
-        let vbox = cascade! {
-            gtk::Box::new(gtk::Orientation::Vertical, 0);
-            ..pack_start(image.as_ref(), true, true, 0);
-            ..add(bottom_bar.as_ref());
+        let metadata = MetadataPanel::new();
+
+        let help = Help::new();
+
+        let picker = Picker::new();
+
+        let overlay = cascade! {
+            gtk::Overlay::new();
+            ..add(image.as_ref());
+            ..add_overlay(help.as_ref());
+            ..add_overlay(picker.as_ref());
+        };
+
+        let content = cascade! {
+            gtk::Box::new(gtk::Orientation::Horizontal, 0);
+            ..pack_start(&overlay, true, true, 0);
+            ..pack_end(metadata.as_ref(), false, false, 0);
         };
 
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let overlay_bar = |valign| {
+            let bar = status_bar.as_ref();
+            bar.set_valign(valign);
+            bar.get_style_context().add_class("osd");
+            overlay.add_overlay(bar);
+            vbox.pack_start(&content, true, true, 0);
+        };
+        match status_position {
+            StatusBarPosition::Top => {
+                vbox.pack_start(status_bar.as_ref(), false, false, 0);
+                vbox.pack_start(&content, true, true, 0);
+            }
+            StatusBarPosition::Bottom => {
+                vbox.pack_start(&content, true, true, 0);
+                vbox.pack_start(status_bar.as_ref(), false, false, 0);
+            }
+            StatusBarPosition::OverlayTop => overlay_bar(gtk::Align::Start),
+            StatusBarPosition::OverlayBottom => overlay_bar(gtk::Align::End),
+        }
+
         Self {
             image,
-            bottom_bar,
+            status_bar,
+            metadata,
+            help,
+            picker,
             vbox,
         }
     }
 
     pub fn set_image(&self, img: Option<&gdk_pixbuf::Pixbuf>) {
+        self.image.broken.hide();
         self.image.image.set_from_pixbuf(img);
     }
 
+    /// Shows a broken image placeholder together with the reason loading failed.
+    pub fn show_broken(&self, err: &str) {
+        self.image
+            .image
+            .set_from_icon_name(Some("image-missing"), gtk::IconSize::Dialog);
+        self.image.broken.set_text(err);
+        self.image.broken.show();
+    }
+
+    /// Calls `f` once the image area stopped changing size for `debounce`.
+    pub fn connect_image_resized<F>(&self, debounce: Duration, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        let f = Rc::new(f);
+        let last_size = Cell::new((0, 0));
+        let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+        self.image.scroll.connect_size_allocate(move |_, alloc| {
+            let size = (alloc.width, alloc.height);
+            if last_size.replace(size) == size {
+                return;
+            }
+
+            if let Some(id) = pending.borrow_mut().take() {
+                glib::source_remove(id);
+            }
+            let f = f.clone();
+            let timeout = pending.clone();
+            let id = glib::timeout_add_local(debounce.as_millis() as u32, move || {
+                timeout.borrow_mut().take();
+                f();
+                glib::Continue(false)
+            });
+            *pending.borrow_mut() = Some(id);
+        });
+    }
+
     pub fn image_allocation(&self) -> Vector2D<i32, Pixels> {
         let alloc = self.image.scroll.get_allocation();
         vec2(alloc.width, alloc.height)
     }
 
     pub fn scroll(&self, scroll: Scroll) {
-        use Scroll::*;
+        scroll_window(&self.image.scroll, scroll);
+    }
+
+    /// Like `scroll` but waits until the current image got its size allocated.
+    pub fn scroll_when_allocated(&self, scroll: Scroll) {
+        let window = self.image.scroll.clone();
+        glib::idle_add_local(move || {
+            scroll_window(&window, scroll);
+            glib::Continue(false)
+        });
+    }
+
+    /// Whether the image can't be scrolled any further in the direction of `scroll`.
+    pub fn at_scroll_edge(&self, scroll: ScrollV) -> bool {
+        let adjust = match self.image.scroll.get_vadjustment() {
+            Some(adjust) => adjust,
+            None => return false,
+        };
         match scroll {
-            H(scroll) => {
-                if let Some(adjust) = self.image.scroll.get_hadjustment() {
-                    use ScrollH::*;
-                    match scroll {
-                        Left => adjust.set_value(adjust.get_value() - adjust.get_step_increment()),
-                        Right => adjust.set_value(adjust.get_value() + adjust.get_step_increment()),
-                        Start => adjust.set_value(adjust.get_lower()),
-                        End => adjust.set_value(adjust.get_upper()),
-                    }
-                }
+            ScrollV::Up | ScrollV::Start => adjust.get_value() <= adjust.get_lower(),
+            ScrollV::Down | ScrollV::End => {
+                adjust.get_value() >= adjust.get_upper() - adjust.get_page_size()
             }
-            V(scroll) => {
-                if let Some(adjust) = self.image.scroll.get_vadjustment() {
-                    use ScrollV::*;
-                    match scroll {
-                        Up => adjust.set_value(adjust.get_value() - adjust.get_step_increment()),
-                        Down => adjust.set_value(adjust.get_value() + adjust.get_step_increment()),
-                        Start => adjust.set_value(adjust.get_lower()),
-                        End => adjust.set_value(adjust.get_upper()),
-                    }
-                }
+        }
+    }
+
+    /// Calls `f` whenever the image gets scrolled vertically.
+    pub fn connect_scrolled<F>(&self, f: F)
+    where
+        F: Fn() + 'static,
+    {
+        if let Some(adjust) = self.image.scroll.get_vadjustment() {
+            adjust.connect_value_changed(move |_| f());
+        }
+    }
+
+    /// Replaces the image with `pages` stacked placeholders of `placeholder_height`.
+    pub fn show_strip(&self, pages: usize, placeholder_height: i32) {
+        let strip = &self.image.strip;
+        for child in strip.get_children() {
+            strip.remove(&child);
+        }
+        let mut strip_pages = self.image.strip_pages.borrow_mut();
+        strip_pages.clear();
+        for _ in 0..pages {
+            let page = cascade! {
+                gtk::Image::new();
+                ..set_size_request(-1, placeholder_height);
+            };
+            strip.pack_start(&page, false, false, 0);
+            strip_pages.push(page);
+        }
+
+        self.image.image.hide();
+        strip.show_all();
+    }
+
+    fn strip_page(&self, page: usize) -> Option<gtk::Image> {
+        self.image.strip_pages.borrow().get(page).cloned()
+    }
+
+    pub fn set_strip_page(&self, page: usize, img: &gdk_pixbuf::Pixbuf) {
+        if let Some(image) = self.strip_page(page) {
+            image.set_size_request(-1, -1);
+            image.set_from_pixbuf(Some(img));
+        }
+    }
+
+    /// Drops the image of `page` but keeps its height so the strip doesn't jump.
+    pub fn clear_strip_page(&self, page: usize) {
+        if let Some(image) = self.strip_page(page) {
+            image.set_size_request(-1, image.get_allocated_height());
+            image.clear();
+        }
+    }
+
+    /// Resizes the placeholder of a page that isn't loaded.
+    pub fn set_strip_page_height(&self, page: usize, height: i32) {
+        if let Some(image) = self.strip_page(page) {
+            image.set_size_request(-1, height);
+        }
+    }
+
+    pub fn set_strip_page_broken(&self, page: usize) {
+        if let Some(image) = self.strip_page(page) {
+            image.set_from_icon_name(Some("image-missing"), gtk::IconSize::Dialog);
+        }
+    }
+
+    /// Pages overlapping the visible part of the strip and the page under its centre.
+    pub fn strip_viewport(&self) -> Option<(Range<usize>, usize)> {
+        let adjust = self.image.scroll.get_vadjustment()?;
+        let top = adjust.get_value() as i32;
+        let bottom = top + adjust.get_page_size() as i32;
+        let centre = (top + bottom) / 2;
+
+        let mut visible: Option<Range<usize>> = None;
+        let mut current = 0;
+        // allocations are relative to the scrolled content
+        for (i, page) in self.image.strip_pages.borrow().iter().enumerate() {
+            let alloc = page.get_allocation();
+            if alloc.y <= centre {
+                current = i;
+            }
+            if alloc.y < bottom && alloc.y + alloc.height > top {
+                visible = Some(visible.map_or(i..i + 1, |range| range.start..i + 1));
+            }
+        }
+
+        Some((visible.unwrap_or(current..current + 1), current))
+    }
+
+    /// Scrolls the top of `page` into view once the strip is laid out.
+    pub fn scroll_to_strip_page(&self, page: usize) {
+        let scroll = self.image.scroll.clone();
+        let page = match self.strip_page(page) {
+            Some(page) => page,
+            None => return,
+        };
+        glib::idle_add_local(move || {
+            if let Some(adjust) = scroll.get_vadjustment() {
+                adjust.set_value(f64::from(page.get_allocation().y));
             }
+            glib::Continue(false)
+        });
+    }
+
+    /// Horizontal and vertical scroll offset of the image.
+    pub fn scroll_position(&self) -> (f64, f64) {
+        let value = |adjust: Option<gtk::Adjustment>| adjust.map(|a| a.get_value()).unwrap_or(0.);
+        (
+            value(self.image.scroll.get_hadjustment()),
+            value(self.image.scroll.get_vadjustment()),
+        )
+    }
+
+    /// Scrolls to `pos` once the current image got its size allocated.
+    pub fn set_scroll_position(&self, (x, y): (f64, f64)) {
+        let scroll = self.image.scroll.clone();
+        glib::idle_add_local(move || {
+            if let Some(adjust) = scroll.get_hadjustment() {
+                adjust.set_value(x);
+            }
+            if let Some(adjust) = scroll.get_vadjustment() {
+                adjust.set_value(y);
+            }
+            glib::Continue(false)
+        });
+    }
+
+    pub fn set_status(&self, segment: Segment, text: &str) {
+        self.status_bar.set_text(segment, text);
+    }
+
+    pub fn set_title(&self, title: &str) {
+        let window = self
+            .vbox
+            .get_toplevel()
+            .and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok());
+        if let Some(window) = window {
+            window.set_title(title);
         }
     }
-    pub fn set_info(&self, text: &str) {
-        self.bottom_bar.info.set_text(text);
+
+    pub fn set_metadata(&self, tags: &[(String, String)]) {
+        self.metadata.set_tags(tags);
+    }
+
+    pub fn toggle_metadata(&self) {
+        self.metadata.toggle();
+    }
+
+    pub fn set_help(&self, bindings: &[(UserEvent, Vec<String>)]) {
+        self.help.set_bindings(bindings);
+    }
+
+    pub fn toggle_help(&self) {
+        self.help.toggle();
+    }
+
+    /// Opens the file picker listing `names`.
+    pub fn show_picker(&self, names: Vec<String>) {
+        self.picker.show(names);
+    }
+
+    /// Calls `f` with the index of the file name chosen in the picker.
+    pub fn connect_picker<F>(&self, f: F)
+    where
+        F: Fn(usize) + 'static,
+    {
+        let picker = self.picker.clone();
+        self.picker.entry.connect_activate(move |_| {
+            if let Some(index) = picker.selected() {
+                picker.hide();
+                f(index);
+            }
+        });
+    }
+
+    pub fn set_loading(&self, loading: bool) {
+        self.status_bar.set_loading(loading);
+    }
+
+    pub fn show_error(&self, text: &str, duration: Duration) {
+        self.status_bar.show_message(text, duration, true);
+    }
+
+    pub fn show_message(&self, text: &str, duration: Duration) {
+        self.status_bar.show_message(text, duration, false);
+    }
+
+    /// Opens the prompt in the status bar and focuses it.
+    pub fn show_prompt(&self, kind: PromptKind) {
+        self.status_bar.prompt.show(kind);
+    }
+
+    /// Calls `f` with the kind and text of the prompt when it's confirmed with Enter.
+    pub fn connect_prompt<F>(&self, f: F)
+    where
+        F: Fn(PromptKind, String) + 'static,
+    {
+        let prompt = self.status_bar.prompt.clone();
+        self.status_bar.prompt.entry.connect_activate(move |entry| {
+            let text = entry.get_text().map(|text| text.to_string());
+            prompt.hide();
+            f(prompt.kind.get(), text.unwrap_or_default());
+        });
     }
 }
@@ -0,0 +1,31 @@
+//! Base directories as described by the XDG base directory specification.
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+fn home_relative(var: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if Path::new(&dir).is_absolute() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| Path::new(&home).join(fallback)),
+    }
+}
+
+pub fn config_home() -> Option<PathBuf> {
+    home_relative("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn state_home() -> Option<PathBuf> {
+    home_relative("XDG_STATE_HOME", ".local/state")
+}
+
+/// System wide config directories, most important first.
+pub fn config_dirs() -> Vec<PathBuf> {
+    let dirs = env::var_os("XDG_CONFIG_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| OsString::from("/etc/xdg"));
+    env::split_paths(&dirs)
+        .filter(|dir| dir.is_absolute())
+        .collect()
+}